@@ -1,6 +1,6 @@
 #[cfg(test)]
 use clap::error::Error as ClapError;
-use clap::{Parser as ClapParser, ValueEnum};
+use clap::{Parser as ClapParser, Subcommand, ValueEnum};
 
 use std::path::PathBuf;
 
@@ -10,8 +10,94 @@ pub enum Language {
     BbcX,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum UndefinedIdentifiers {
+    Allocate,
+    Warn,
+    Strict,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum LiteralPool {
+    Shared,
+    PerUse,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum MemoryFill {
+    Undefined,
+    Zero,
+    Poison,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum InputDevice {
+    Console,
+    PaperTape,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum OutputDevice {
+    Console,
+    Teletype,
+    LinePrinter,
+    PaperTape,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum TapeFrame {
+    Five,
+    Six,
+}
+
+#[derive(Clone, Debug, PartialEq, Subcommand)]
+pub enum Command {
+    /// Disassemble object files, or the memory dumped at the end of a trace file, back into
+    /// BBC-X source. The source is written to standard output.
+    Disasm {
+        /// The object file(s), named '<FILE>.obj', or trace file(s), named '<FILE>.out', to
+        /// disassemble.
+        #[arg(required(true))]
+        files: Vec<PathBuf>,
+    },
+    /// Reconstruct readable BBC-X source from an assembled listing that uses numeric
+    /// addresses, with labels, identifiers and literal operands in place of the addresses.
+    /// The source is written to standard output.
+    Reconstruct {
+        /// The listing file(s) to reconstruct.
+        #[arg(required(true))]
+        files: Vec<PathBuf>,
+    },
+    /// Format source files into the columns of a listing, keeping comments. Each file is
+    /// rewritten in place, unless it is only checked.
+    Fmt {
+        /// Check that the files are formatted, without rewriting them. Each line that is not
+        /// formatted is reported as an error.
+        #[arg(long)]
+        check: bool,
+
+        /// The source file(s) to format.
+        #[arg(required(true))]
+        files: Vec<PathBuf>,
+    },
+    /// Check programs for likely mistakes without running them. Each warning has a code,
+    /// such as L001, and is suppressed by NOLINT in the comment of its line.
+    Lint {
+        /// The source file(s) to check.
+        #[arg(required(true))]
+        files: Vec<PathBuf>,
+    },
+    /// Write the control-flow graph of programs as a Graphviz DOT digraph, with a node for
+    /// each basic block. The graph is written to standard output.
+    Cfg {
+        /// The source or object file(s) to graph.
+        #[arg(required(true))]
+        files: Vec<PathBuf>,
+    },
+}
+
 #[derive(Clone, Debug, ClapParser, PartialEq)]
-#[command(version, about, long_about = None)]
+#[command(version, about, long_about = None, subcommand_negates_reqs = true)]
 ///
 /// Resurrection of the educational BBC-X assembler language used at Hatfield Polytechnic.
 ///
@@ -31,8 +117,19 @@ pub struct Args {
     #[arg(long)]
     list_path: Option<PathBuf>,
 
+    /// Write the assembled memory image of each file to an object file named '<FILE>.obj'.
+    /// See also [object-path].
+    #[arg(long, required(false))]
+    object: bool,
+
+    /// The folder where the object files will be written. If not specified then they will be
+    /// written to the same folder as the input file. Implies '--object'.
+    #[arg(long)]
+    object_path: Option<PathBuf>,
+
     /// Run the file(s) following successfully compillation. If more than one file is provided
-    /// then each will be run sequentially.
+    /// then each will be run sequentially. Object files, named '<FILE>.obj', are loaded and
+    /// run without being assembled.
     #[arg(short, long, required(false))]
     run: bool,
 
@@ -46,9 +143,97 @@ pub struct Args {
     #[arg(long)]
     trace_path: Option<PathBuf>,
 
+    /// How identifiers that are not defined by a label are treated. They can be silently
+    /// allocated storage, allocated with a warning listing their addresses and uses, or
+    /// reported as errors.
+    #[arg(long, value_enum, default_value_t = UndefinedIdentifiers::Allocate)]
+    undefined_identifiers: UndefinedIdentifiers,
+
+    /// How literal operands are stored. Identical literals can share one word of storage,
+    /// or each use can be given its own word as in the original assembler.
+    #[arg(long, value_enum, default_value_t = LiteralPool::Shared)]
+    literal_pool: LiteralPool,
+
+    /// Print a map of the program words and of the storage allocated for identifiers and
+    /// literals when a file is assembled.
+    #[arg(long, required(false))]
+    storage_map: bool,
+
+    /// A folder to search for files named by an INCLUDE directive, after the folder of the
+    /// including file. Can be given more than once; the folders are searched in order.
+    #[arg(short = 'I', long)]
+    include_path: Vec<PathBuf>,
+
+    /// Link all the source files into one program, rather than assembling each on its own.
+    /// Each file is a module: identifiers named by EXPORT can be used by the modules that
+    /// IMPORT them, and all others are private to their module. The modules are placed one
+    /// after another in the order given, and the program starts in the first.
+    #[arg(long, required(false))]
+    link: bool,
+
+    /// The number of words of memory available to a BBC-X program when it is run. Storage for
+    /// literals and undefined identifiers is allocated downwards from the top of this memory.
+    #[arg(long, default_value_t = 1024, value_parser = clap::value_parser!(u16).range(8..=1024))]
+    memory_size: u16,
+
+    /// The initial content of memory locations that are not loaded by the program.
+    #[arg(long, value_enum, default_value_t = MemoryFill::Undefined)]
+    memory_fill: MemoryFill,
+
+    /// The device that supplies the input of a BBC-X program when it is run.
+    #[arg(long, value_enum, default_value_t = InputDevice::Console)]
+    input: InputDevice,
+
+    /// The device that receives the output of a BBC-X program when it is run.
+    #[arg(long, value_enum, default_value_t = OutputDevice::Console)]
+    output: OutputDevice,
+
+    /// The paper tape image read when the input device is 'paper-tape', or when the program
+    /// selects the reader with READER 1.
+    #[arg(long, required_if_eq("input", "paper-tape"))]
+    tape_reader: Option<PathBuf>,
+
+    /// The paper tape image punched when the output device is 'paper-tape', or when the program
+    /// selects the punch with PUNCH 1.
+    #[arg(long, required_if_eq("output", "paper-tape"))]
+    tape_punch: Option<PathBuf>,
+
+    /// The number of holes in each frame of a paper tape image.
+    #[arg(long, value_enum, default_value_t = TapeFrame::Six)]
+    tape_frame: TapeFrame,
+
+    /// The number of characters printed on a teletype line before the carriage is returned.
+    #[arg(long, default_value_t = 72, value_parser = clap::value_parser!(u16).range(1..))]
+    carriage_width: u16,
+
+    /// Print teletype output at the original 10 characters per second.
+    #[arg(long, required(false))]
+    throttle: bool,
+
+    /// The number of lines printed on a line printer page, including any page header. Listing
+    /// files are laid out in pages of the same length.
+    #[arg(long, default_value_t = 66, value_parser = clap::value_parser!(u16).range(3..))]
+    page_length: u16,
+
+    /// The number of characters printed on a line printer line before it is folded. Listing
+    /// files are laid out in pages of the same width.
+    #[arg(long, default_value_t = 132, value_parser = clap::value_parser!(u16).range(1..))]
+    page_width: u16,
+
+    /// The title printed in the header of each line printer page.
+    #[arg(long)]
+    page_title: Option<String>,
+
+    /// Number the line printer pages in the page header.
+    #[arg(long, required(false))]
+    page_numbers: bool,
+
     /// The source file(s) to be compiled and / or run.
     #[arg(required(true))]
     files: Vec<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
 impl Args {
@@ -60,6 +245,11 @@ impl Args {
         self.language
     }
 
+    #[inline]
+    pub fn command(&self) -> Option<&Command> {
+        self.command.as_ref()
+    }
+
     #[inline]
     pub fn list(&self) -> bool {
         self.list | self.list_path().is_some()
@@ -70,6 +260,16 @@ impl Args {
         self.list_path.clone()
     }
 
+    #[inline]
+    pub fn object(&self) -> bool {
+        self.object | self.object_path().is_some()
+    }
+
+    #[inline]
+    pub fn object_path(&self) -> Option<PathBuf> {
+        self.object_path.clone()
+    }
+
     #[inline]
     pub fn trace(&self) -> bool {
         self.trace | self.trace_path().is_some()
@@ -90,6 +290,96 @@ impl Args {
         self.run | self.trace()
     }
 
+    #[inline]
+    pub fn undefined_identifiers(&self) -> UndefinedIdentifiers {
+        self.undefined_identifiers
+    }
+
+    #[inline]
+    pub fn literal_pool(&self) -> LiteralPool {
+        self.literal_pool
+    }
+
+    #[inline]
+    pub fn storage_map(&self) -> bool {
+        self.storage_map
+    }
+
+    #[inline]
+    pub fn include_paths(&self) -> Vec<PathBuf> {
+        self.include_path.clone()
+    }
+
+    #[inline]
+    pub fn link(&self) -> bool {
+        self.link
+    }
+
+    #[inline]
+    pub fn memory_size(&self) -> usize {
+        self.memory_size as usize
+    }
+
+    #[inline]
+    pub fn memory_fill(&self) -> MemoryFill {
+        self.memory_fill
+    }
+
+    #[inline]
+    pub fn input(&self) -> InputDevice {
+        self.input
+    }
+
+    #[inline]
+    pub fn output(&self) -> OutputDevice {
+        self.output
+    }
+
+    #[inline]
+    pub fn tape_reader(&self) -> Option<PathBuf> {
+        self.tape_reader.clone()
+    }
+
+    #[inline]
+    pub fn tape_punch(&self) -> Option<PathBuf> {
+        self.tape_punch.clone()
+    }
+
+    #[inline]
+    pub fn tape_frame(&self) -> TapeFrame {
+        self.tape_frame
+    }
+
+    #[inline]
+    pub fn carriage_width(&self) -> usize {
+        self.carriage_width as usize
+    }
+
+    #[inline]
+    pub fn throttle(&self) -> bool {
+        self.throttle
+    }
+
+    #[inline]
+    pub fn page_length(&self) -> usize {
+        self.page_length as usize
+    }
+
+    #[inline]
+    pub fn page_width(&self) -> usize {
+        self.page_width as usize
+    }
+
+    #[inline]
+    pub fn page_title(&self) -> Option<String> {
+        self.page_title.clone()
+    }
+
+    #[inline]
+    pub fn page_numbers(&self) -> bool {
+        self.page_numbers
+    }
+
     #[cfg(test)]
     pub fn try_from(args: &str) -> Result<Self, ClapError> {
         let args = Vec::from_iter(args.split(' ').map(String::from));
@@ -106,28 +396,37 @@ mod test {
     #[test]
     fn error_with_zero_args() {
         let args = "bbc-x";
-        let result = Args::try_from(&args).map_err(|e| e.kind());
+        let result = Args::try_from(args).map_err(|e| e.kind());
         assert_eq!(result, Err(ErrorKind::MissingRequiredArgument))
     }
 
     #[test]
     fn use_source_files_1() {
         let args = "bbc-x infile1.bbc";
-        let result = Args::try_from(&args).expect("Expected successful parse");
+        let result = Args::try_from(args).expect("Expected successful parse");
         let files = result.files;
         assert_eq!(files.len(), 1);
         assert_eq!(files[0], PathBuf::new().join("infile1.bbc"));
         assert!(!result.list);
         assert_eq!(result.list_path, None);
         assert!(!result.run);
+        assert!(!result.link);
         assert!(!result.trace);
         assert_eq!(result.trace_path, None);
+        assert_eq!(result.undefined_identifiers, UndefinedIdentifiers::Allocate);
+        assert_eq!(result.literal_pool, LiteralPool::Shared);
+        assert_eq!(result.memory_size, 1024);
+        assert_eq!(result.memory_fill, MemoryFill::Undefined);
+        assert_eq!(result.input, InputDevice::Console);
+        assert_eq!(result.output, OutputDevice::Console);
+        assert_eq!(result.tape_frame, TapeFrame::Six);
+        assert!(!result.throttle);
     }
 
     #[test]
     fn use_source_files_n() {
         let args = "bbc-x infile1.bbc infile2.bbc infile3.bbc";
-        let result = Args::try_from(&args).expect("Expected successful parse");
+        let result = Args::try_from(args).expect("Expected successful parse");
         let files = result.files;
         assert_eq!(files.len(), 3);
         assert_eq!(files[0], PathBuf::new().join("infile1.bbc"));
@@ -143,38 +442,214 @@ mod test {
     #[test]
     fn use_list() {
         let args = "bbc-x --list infile1.bbc";
-        let result = Args::try_from(&args).expect("Expected successful parse");
+        let result = Args::try_from(args).expect("Expected successful parse");
         assert!(result.list)
     }
 
     #[test]
     fn use_list_path() {
         let args = "bbc-x --list-path my/list/path/ infile1.bbc";
-        let result = Args::try_from(&args).expect("Expected successful parse");
+        let result = Args::try_from(args).expect("Expected successful parse");
         assert_eq!(result.list_path, Some(PathBuf::new().join("my/list/path/")))
     }
 
     #[test]
     fn use_run() {
         let args = "bbc-x --run infile1.bbc";
-        let result = Args::try_from(&args).expect("Expected successful parse");
+        let result = Args::try_from(args).expect("Expected successful parse");
         assert!(result.run)
     }
 
     #[test]
     fn use_trace() {
         let args = "bbc-x --trace infile1.bbc";
-        let result = Args::try_from(&args).expect("Expected successful parse");
+        let result = Args::try_from(args).expect("Expected successful parse");
         assert!(result.trace)
     }
 
     #[test]
     fn use_trace_path() {
         let args = "bbc-x --trace-path my/trace/path/ infile1.bbc";
-        let result = Args::try_from(&args).expect("Expected successful parse");
+        let result = Args::try_from(args).expect("Expected successful parse");
         assert_eq!(
             result.trace_path,
             Some(PathBuf::new().join("my/trace/path/"))
         )
     }
+
+    #[test]
+    fn use_undefined_identifiers() {
+        let args = "bbc-x --undefined-identifiers strict infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.undefined_identifiers(), UndefinedIdentifiers::Strict)
+    }
+
+    #[test]
+    fn use_literal_pool() {
+        let args = "bbc-x --literal-pool per-use infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.literal_pool(), LiteralPool::PerUse)
+    }
+
+    #[test]
+    fn use_storage_map() {
+        let args = "bbc-x --storage-map infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert!(result.storage_map())
+    }
+
+    #[test]
+    fn use_include_paths() {
+        let args = "bbc-x -I lib --include-path /usr/share/bbcx infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(
+            result.include_paths(),
+            vec![PathBuf::from("lib"), PathBuf::from("/usr/share/bbcx")]
+        )
+    }
+
+    #[test]
+    fn use_object_path() {
+        let args = "bbc-x --object-path objects infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert!(result.object());
+        assert_eq!(result.object_path(), Some(PathBuf::from("objects")))
+    }
+
+    #[test]
+    fn use_disasm_command() {
+        let args = "bbc-x --lang bbc-x disasm prog.obj run.out";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.files().count(), 0);
+        assert_eq!(
+            result.command(),
+            Some(&Command::Disasm {
+                files: vec![PathBuf::from("prog.obj"), PathBuf::from("run.out")]
+            })
+        )
+    }
+
+    #[test]
+    fn error_with_disasm_command_without_files() {
+        let args = "bbc-x disasm";
+        let result = Args::try_from(args).map_err(|e| e.kind());
+        assert_eq!(result, Err(ErrorKind::MissingRequiredArgument))
+    }
+
+    #[test]
+    fn use_reconstruct_command() {
+        let args = "bbc-x reconstruct sort.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(
+            result.command(),
+            Some(&Command::Reconstruct {
+                files: vec![PathBuf::from("sort.bbc")]
+            })
+        )
+    }
+
+    #[test]
+    fn use_fmt_command_with_check() {
+        let args = "bbc-x --lang=bbc3 fmt --check nthg.bbc sort.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.language(), Language::Bbc3);
+        assert_eq!(
+            result.command(),
+            Some(&Command::Fmt {
+                check: true,
+                files: vec![PathBuf::from("nthg.bbc"), PathBuf::from("sort.bbc")]
+            })
+        )
+    }
+
+    #[test]
+    fn use_lint_command() {
+        let args = "bbc-x lint sqrt.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(
+            result.command(),
+            Some(&Command::Lint {
+                files: vec![PathBuf::from("sqrt.bbc")]
+            })
+        )
+    }
+
+    #[test]
+    fn use_cfg_command() {
+        let args = "bbc-x cfg base.bbc base.obj";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(
+            result.command(),
+            Some(&Command::Cfg {
+                files: vec![PathBuf::from("base.bbc"), PathBuf::from("base.obj")]
+            })
+        )
+    }
+
+    #[test]
+    fn use_link() {
+        let args = "bbc-x --link main.bbc lib.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert!(result.link());
+        assert_eq!(result.files().count(), 2)
+    }
+
+    #[test]
+    fn use_memory_size() {
+        let args = "bbc-x --memory-size 512 infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.memory_size(), 512)
+    }
+
+    #[test]
+    fn error_with_memory_size_out_of_range() {
+        let args = "bbc-x --memory-size 2048 infile1.bbc";
+        let result = Args::try_from(args).map_err(|e| e.kind());
+        assert_eq!(result, Err(ErrorKind::ValueValidation))
+    }
+
+    #[test]
+    fn use_memory_fill() {
+        let args = "bbc-x --memory-fill poison infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.memory_fill(), MemoryFill::Poison)
+    }
+
+    #[test]
+    fn use_output_teletype() {
+        let args = "bbc-x --output teletype --carriage-width 64 --throttle infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.output(), OutputDevice::Teletype);
+        assert_eq!(result.carriage_width(), 64);
+        assert!(result.throttle())
+    }
+
+    #[test]
+    fn use_output_line_printer() {
+        let args = "bbc-x --output line-printer --page-length 60 --page-width 80 --page-title SQRT --page-numbers infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.output(), OutputDevice::LinePrinter);
+        assert_eq!(result.page_length(), 60);
+        assert_eq!(result.page_width(), 80);
+        assert_eq!(result.page_title(), Some("SQRT".into()));
+        assert!(result.page_numbers())
+    }
+
+    #[test]
+    fn use_paper_tape() {
+        let args = "bbc-x --input paper-tape --tape-reader in.tape --output paper-tape --tape-punch out.tape --tape-frame five infile1.bbc";
+        let result = Args::try_from(args).expect("Expected successful parse");
+        assert_eq!(result.input(), InputDevice::PaperTape);
+        assert_eq!(result.output(), OutputDevice::PaperTape);
+        assert_eq!(result.tape_reader(), Some(PathBuf::new().join("in.tape")));
+        assert_eq!(result.tape_punch(), Some(PathBuf::new().join("out.tape")));
+        assert_eq!(result.tape_frame(), TapeFrame::Five)
+    }
+
+    #[test]
+    fn error_with_paper_tape_without_image() {
+        let args = "bbc-x --input paper-tape infile1.bbc";
+        let result = Args::try_from(args).map_err(|e| e.kind());
+        assert_eq!(result, Err(ErrorKind::MissingRequiredArgument))
+    }
 }
@@ -35,7 +35,7 @@ fn validate_ast(ast: &[SourceProgramLine]) -> Result<()> {
             counts
         })
         .into_iter()
-        .filter(|&(_key, value)| (value > 1))
+        .filter(|&(_key, value)| value > 1)
         .map(|(key, _value)| key)
         .collect::<Vec<_>>();
 
@@ -74,7 +74,7 @@ mod test {
         input
             .to_string()
             .lines()
-            .map(|l| Parser::parse_line(l))
+            .map(Parser::parse_line)
             .filter_map(Result::ok)
             .collect::<SourceProgram>()
     }
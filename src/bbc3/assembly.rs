@@ -19,6 +19,6 @@ impl Assembly {
 
     #[cfg(test)]
     pub fn content(&self, location: Location) -> Option<Content> {
-        self._code.get(&location).map(|w| w.clone())
+        self._code.get(&location).cloned()
     }
 }
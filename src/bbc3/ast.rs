@@ -55,7 +55,7 @@ impl std::fmt::Display for SourceProgramWord {
         match self {
             SourceProgramWord::SWord(sword) => write!(f, "<{}>", sword),
             SourceProgramWord::PWord(pword) => write!(f, "{}", pword),
-            SourceProgramWord::FWord(fword) => write!(f, "{}", fword),
+            SourceProgramWord::FWord(fword) => write!(f, "{}", float_text(format!("{}", fword))),
             SourceProgramWord::IWord(iword) => write!(f, "{}", iword),
             SourceProgramWord::Octal(octal) => write!(f, "{}", octal),
         }
@@ -64,6 +64,15 @@ impl std::fmt::Display for SourceProgramWord {
 
 pub type SWord = String;
 
+// A whole number is written with a fraction, so that it is read back as an F-word.
+fn float_text(text: String) -> String {
+    if text.contains('.') {
+        text
+    } else {
+        text + ".0"
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum PWord {
     TakeType(Mnemonic, Acc, GeneralOperand),
@@ -212,7 +221,7 @@ impl std::fmt::Display for ConstOperand {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
         match self {
             ConstOperand::SignedInteger(c) => write!(f, "{:+}", c),
-            ConstOperand::SignedFWord(c) => write!(f, "{:+}", c),
+            ConstOperand::SignedFWord(c) => write!(f, "{}", float_text(format!("{:+}", c))),
             ConstOperand::Octal(c) => write!(f, "{}", c),
             ConstOperand::SWord(c) => write!(f, "<{}>", c),
         }
@@ -0,0 +1,81 @@
+use super::parser::Parser;
+
+use crate::result::{Error, Result};
+
+// ****************************************************************************
+// Source is formatted into the columns of a listing: location, word, then
+// comment. Blank lines are kept. A formatted line must be read back as the
+// line it was made from, so formatting never changes the program.
+
+/// The formatted text of the lines of a file.
+pub fn format(lines: &[String]) -> Result<Vec<String>> {
+    let mut formatted = Vec::new();
+    let mut errors = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        match format_line(line.trim_end()) {
+            Ok(text) => formatted.push(text),
+            Err(error) => errors.push(format!("Line {}: {}", i + 1, error)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(formatted)
+    } else {
+        Err(Error::FailedToFormat(errors))
+    }
+}
+
+fn format_line(text: &str) -> std::result::Result<String, String> {
+    if text.trim().is_empty() {
+        return Ok(String::new());
+    }
+
+    let line = Parser::parse_line(text).map_err(|error| error.to_string())?;
+    let formatted = line.to_string().trim_end().to_string();
+
+    match Parser::parse_line(&formatted) {
+        Ok(formatted_line) if formatted_line == line => Ok(formatted),
+        _ => Err(format!("\"{}\" would not be read back the same", formatted)),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn will_format_into_columns() {
+        let lines = [
+            "0001   NTHG    HERE",
+            "0002 ADD 2 ADDR1:42    ADD INDEXED",
+            "",
+            "0003\t<ABCD>",
+            "0004    LDN   2 *ADDR2:57",
+            "0005 100.0",
+            "0006 SIN",
+        ]
+        .map(String::from);
+        assert_eq!(
+            format(&lines).unwrap(),
+            vec![
+                "0001    NTHG       HERE",
+                "0002    ADD      2 ADDR1:42                       ADD INDEXED",
+                "",
+                "0003    <ABCD>",
+                "0004    LDN      2 *ADDR2:57",
+                "0005    100.0",
+                "0006    SIN",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_not_format_invalid_lines() {
+        let lines = ["0001 NTHG HERE", "NTHG"].map(String::from);
+        let result = format(&lines);
+        assert!(
+            matches!(result, Err(Error::FailedToFormat(errors)) if errors == ["Line 2: failed to parse NTHG"])
+        );
+    }
+}
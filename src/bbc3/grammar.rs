@@ -350,11 +350,13 @@ fn unsigned_fword<'a>() -> Parser<'a, FWord> {
 // <decimal part> ::= <unsigned integer>.<unsigned integer> |
 //                    .<unsigned integer>
 fn decimal_part<'a>() -> Parser<'a, String> {
-    let if_to_string = |(i, f): (IntType, IntType)| format!("{}.{}", i, f);
-    let f_to_string = |f: IntType| format!("0.{}", f);
+    let if_to_string = |(i, f): (IntType, String)| format!("{}.{}", i, f);
+    let f_to_string = |f: String| format!("0.{}", f);
+    // The fraction is kept as digits, so that its leading zeros are not lost.
+    let fraction = || digit().repeat(1..).map(String::from_iter);
 
-    ((unsigned_integer() - sym('.').discard() + unsigned_integer()).map(if_to_string)
-        | (sym('.').discard() * unsigned_integer()).map(f_to_string))
+    ((unsigned_integer() - sym('.').discard() + fraction()).map(if_to_string)
+        | (sym('.').discard() * fraction()).map(f_to_string))
     .name("decimal_part")
 }
 
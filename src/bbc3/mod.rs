@@ -1,6 +1,7 @@
 mod assembler;
 mod assembly;
 mod ast;
+mod formatter;
 mod grammar;
 mod parser;
 
@@ -13,8 +14,9 @@ use crate::args::Args;
 use crate::list_writer::ListWriter;
 use crate::model::*;
 use crate::result::{Error, Result};
+use crate::source_writer::write_formatted;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub struct Bbc3 {
     args: Args,
@@ -71,13 +73,14 @@ impl Bbc3 {
         let mut writer = ListWriter::new(path, &self.args);
         let lines = file_lines(path)?;
         let results = lines.iter().map(|line| Parser::parse_line(line));
-        for line in results {
-            let line = match line {
-                Ok(line) => format!("        {}", line),
-                Err(Error::FailedToParse(error)) => format!(" *****  {}", error),
+        for (i, line) in results.enumerate() {
+            match line {
+                Ok(line) => writer.add_source_to_listing(i + 1, &format!("        {}", line)),
+                Err(Error::FailedToParse(error)) => {
+                    writer.add_error_to_listing(Some(i + 1), &error)
+                }
                 _ => unreachable!(),
-            };
-            writer.add_lines_to_listing(&line);
+            }
         }
         writer
             .write_content_to_file()
@@ -112,6 +115,48 @@ impl LanguageModel for Bbc3 {
         _ = self.impl_list(path);
         Ok(())
     }
+
+    fn link(&self, _paths: &[PathBuf]) -> Result<()> {
+        Err(Error::FailedToAssemble(vec![
+            "BBC-3 link command is not implemented".into(),
+        ]))
+    }
+
+    fn run_linked(&self, _paths: &[PathBuf], _trace: Option<&Path>) -> Result<()> {
+        Err(Error::FailedToRun(
+            "BBC-3 run command is not implemented".into(),
+        ))
+    }
+
+    fn disassemble(&self, _path: &Path) -> Result<()> {
+        Err(Error::FailedToDisassemble(vec![
+            "BBC-3 disasm command is not implemented".into(),
+        ]))
+    }
+
+    fn reconstruct(&self, _path: &Path) -> Result<()> {
+        Err(Error::FailedToDisassemble(vec![
+            "BBC-3 reconstruct command is not implemented".into(),
+        ]))
+    }
+
+    fn format(&self, path: &Path, check: bool) -> Result<()> {
+        let lines = file_lines(path)?;
+        let formatted = formatter::format(&lines)?;
+        write_formatted(path, &lines, &formatted, check)
+    }
+
+    fn lint(&self, _path: &Path) -> Result<()> {
+        Err(Error::FailedToAssemble(vec![
+            "BBC-3 lint command is not implemented".into(),
+        ]))
+    }
+
+    fn graph(&self, _path: &Path) -> Result<()> {
+        Err(Error::FailedToAssemble(vec![
+            "BBC-3 cfg command is not implemented".into(),
+        ]))
+    }
 }
 
 #[cfg(test)]
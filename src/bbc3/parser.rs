@@ -14,6 +14,7 @@ impl Parser {
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod test {
     use super::*;
     use pretty_assertions::assert_eq;
@@ -291,6 +292,28 @@ mod test {
         assert_eq!(actual[1..], expected)
     }
 
+    #[test]
+    fn fword_keeps_leading_zeros_of_fraction() {
+        let program = r#"
+0001   1.05
+0002   .005
+"#;
+        let actual = parse(program);
+        let expected = vec![
+            Ok(SourceProgramLine::new(
+                1,
+                SourceProgramWord::FWord(1.05),
+                "".into(),
+            )),
+            Ok(SourceProgramLine::new(
+                2,
+                SourceProgramWord::FWord(0.005),
+                "".into(),
+            )),
+        ];
+        assert_eq!(actual[1..], expected)
+    }
+
     #[test]
     fn iword() {
         let program = r#"
@@ -1,35 +1,264 @@
-use super::assembly::{Assembly, Code, Symbols};
-use super::ast::SourceLine;
+use super::assembly::{Assembly, Code, LiteralPool, Location, Symbols};
+use super::ast::{
+    Address, AddressOperand, Directive, Identifier, Mnemonic, Position, SimpleAddressOperand,
+    SourceLine, SourceWord, StoreOperand,
+};
+use super::memory::{Accumulator, IndexRegister, MemoryConfig};
 
 use crate::result::{Error, Result};
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
+
+/// The location counter starts at the first word after the accumulators.
+pub(super) const DEFAULT_ORIGIN: Location = 8;
+
+/// How identifiers that are never defined by a label are treated. By default
+/// each one is silently allocated a word of storage.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum UndefinedIdentifiers {
+    #[default]
+    Allocate,
+    Warn,
+    Strict,
+}
 
 #[derive(Debug, PartialEq)]
-pub struct Assembler {}
+pub struct Assembler {
+    undefined_identifiers: UndefinedIdentifiers,
+    literal_pool: LiteralPool,
+    memory_size: usize,
+}
+
+impl Default for Assembler {
+    fn default() -> Self {
+        Self {
+            undefined_identifiers: UndefinedIdentifiers::default(),
+            literal_pool: LiteralPool::default(),
+            memory_size: MemoryConfig::default().size(),
+        }
+    }
+}
 
 impl Assembler {
+    #[cfg(test)]
     pub fn assemble(ast: &[SourceLine]) -> Result<Assembly> {
-        validate_ast(ast)?;
+        Assembler::default().build(ast)
+    }
+
+    pub fn with_undefined_identifiers(
+        mut self,
+        undefined_identifiers: UndefinedIdentifiers,
+    ) -> Self {
+        self.undefined_identifiers = undefined_identifiers;
+        self
+    }
+
+    pub fn with_literal_pool(mut self, literal_pool: LiteralPool) -> Self {
+        self.literal_pool = literal_pool;
+        self
+    }
+
+    pub fn with_memory_size(mut self, memory_size: usize) -> Self {
+        self.memory_size = memory_size;
+        self
+    }
+
+    pub fn build(&self, ast: &[SourceLine]) -> Result<Assembly> {
+        validate_free_form(ast)?;
+        let ast = &resolve_locations(ast);
+        self.validate_reserved(ast)?;
+        all_of(vec![
+            validate_ast(ast),
+            validate_operands(ast, self.memory_size),
+        ])?;
+        let assembly = self.assembly(ast);
+        validate_externals(ast, &assembly, false)?;
+        let allocated = assembly.clone().allocate_storage(self.memory_size)?;
+        let assembly = self.check_undefined_identifiers(ast, assembly, Some(&allocated))?;
+        let lines = ast
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| line.source_program_word().is_some())
+            .filter_map(|(i, line)| {
+                line.location()
+                    .map(|location| (location, position(i, line)))
+            })
+            .collect::<HashMap<_, _>>();
+        validate_resolved_operands(&allocated, self.memory_size, |location| {
+            format!("Line {}", lines[&location])
+        })?;
+        Ok(assembly)
+    }
+
+    /// Assemble one of the modules of a program. Storage is allocated, and address
+    /// expressions are evaluated, once the modules have been linked.
+    pub fn build_module(&self, ast: &[SourceLine]) -> Result<Assembly> {
+        validate_free_form(ast)?;
+        let ast = &resolve_locations(ast);
+        self.validate_reserved(ast)?;
+        all_of(vec![
+            validate_ast(ast),
+            validate_operands(ast, self.memory_size),
+        ])?;
+        let assembly = self.assembly(ast);
+        validate_externals(ast, &assembly, true)?;
+        self.check_undefined_identifiers(ast, assembly, None)
+    }
+
+    fn assembly(&self, ast: &[SourceLine]) -> Assembly {
         let code = generate_code(ast);
         let symbols = generate_symbol_table(ast);
-        let assembly = Assembly::new(&code, &symbols);
-        Ok(assembly)
+        let (exports, imports) = externals(ast);
+        Assembly::new(&code, &symbols)
+            .with_reserved(reserved_blocks(ast))
+            .with_constants(constants(ast))
+            .with_externals(exports, imports)
+            .with_literal_pool(self.literal_pool)
+    }
+
+    // A reserved block must fit in memory, and is rejected before its locations
+    // are checked, as it can be of any size.
+    fn validate_reserved(&self, ast: &[SourceLine]) -> Result<()> {
+        let errors = ast
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| match (line.location(), line.directive()) {
+                (Some(location), Some(Directive::Reserve(size)))
+                    if location.saturating_add(*size) > self.memory_size =>
+                {
+                    Some(format!(
+                        "Line {}: RESERVE {} at {:04} runs past the end of memory",
+                        position(i, line),
+                        size,
+                        location
+                    ))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::FailedToAssemble(errors))
+        }
+    }
+
+    fn check_undefined_identifiers(
+        &self,
+        ast: &[SourceLine],
+        assembly: Assembly,
+        allocated: Option<&Assembly>,
+    ) -> Result<Assembly> {
+        let uses = undefined_identifier_uses(ast, &assembly);
+        match self.undefined_identifiers {
+            UndefinedIdentifiers::Allocate => Ok(assembly),
+            UndefinedIdentifiers::Warn => {
+                let warnings = uses
+                    .iter()
+                    .map(|(identifier, lines)| match allocated {
+                        Some(allocated) => format!(
+                            "Identifier {} allocated at {:04}, used on lines {}",
+                            identifier,
+                            allocated.location(identifier).unwrap(),
+                            join_lines(lines)
+                        ),
+                        None => format!(
+                            "Identifier {} allocated when linked, used on lines {}",
+                            identifier,
+                            join_lines(lines)
+                        ),
+                    })
+                    .collect::<Vec<_>>();
+                Ok(assembly.with_warnings(warnings))
+            }
+            UndefinedIdentifiers::Strict if uses.is_empty() => Ok(assembly),
+            UndefinedIdentifiers::Strict => {
+                let errors = uses
+                    .iter()
+                    .map(|(identifier, lines)| {
+                        format!(
+                            "Undefined identifier {}, used on lines {}",
+                            identifier,
+                            join_lines(lines)
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                Err(Error::FailedToAssemble(errors))
+            }
+        }
     }
 }
 
-fn validate_ast(ast: &[SourceLine]) -> Result<()> {
-    let mut invalid_locations = ast
+// Lines without a location are placed by a location counter. The counter is set
+// by an explicit location or an ORIGIN directive, and advances past each word,
+// reserved block and text. A label on a line without a word names the next
+// location, unless it is defined by EQU.
+pub(super) fn resolve_locations(ast: &[SourceLine]) -> Vec<SourceLine> {
+    let mut counter = DEFAULT_ORIGIN;
+    ast.iter()
+        .map(|line| {
+            if let Some(location) = line.location() {
+                counter = location;
+            }
+            if let Some(Directive::Origin(origin)) = line.directive() {
+                counter = *origin;
+            }
+            let is_constant = matches!(line.directive(), Some(Directive::Equ(_)));
+            let placed = line.size() > 0 || (line.label().name().is_some() && !is_constant);
+            let line = if placed {
+                line.clone().with_location(counter)
+            } else {
+                line.clone()
+            };
+            counter = counter.saturating_add(line.size());
+            line
+        })
+        .collect()
+}
+
+// In source placed by the location counter, an unsigned number followed only by
+// a comment reads as a location with no word, although it was probably meant as
+// an I-word. Such lines are rejected; an I-word must then be written with a sign.
+// Source in which every word has a location can still use a location alone,
+// such as to comment out its word.
+fn validate_free_form(ast: &[SourceLine]) -> Result<()> {
+    let is_free_form = ast
         .iter()
-        .fold(HashMap::new(), |mut counts, line| {
-            *counts.entry(line.location()).or_insert(0) += 1;
-            counts
+        .any(|line| line.location().is_none() && line.size() > 0);
+    if !is_free_form {
+        return Ok(());
+    }
+
+    let errors = ast
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let is_location_only = line.label().name().is_none()
+                && line.source_program_word().is_none()
+                && line.directive().is_none();
+            match line.location() {
+                Some(location) if is_location_only => Some(format!(
+                    "Line {}: location {} has no word; write an I-word with a sign, such as +{}",
+                    position(i, line),
+                    location,
+                    location
+                )),
+                _ => None,
+            }
         })
-        .into_iter()
-        .filter(|&(_key, value)| (value > 1))
-        .map(|(key, _value)| key)
         .collect::<Vec<_>>();
 
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::FailedToAssemble(errors))
+    }
+}
+
+fn validate_ast(ast: &[SourceLine]) -> Result<()> {
+    let invalid_locations = overlapping_locations(ast);
+
     let mut invalid_labels = ast
         .iter()
         .fold(HashMap::new(), |mut counts, line| {
@@ -37,14 +266,13 @@ fn validate_ast(ast: &[SourceLine]) -> Result<()> {
             counts
         })
         .into_iter()
-        .filter(|&(key, value)| (key.name().is_some() && value > 1))
+        .filter(|&(key, value)| key.name().is_some() && value > 1)
         .map(|(key, _value)| key)
         .collect::<Vec<_>>();
 
     if invalid_locations.is_empty() && invalid_labels.is_empty() {
         Ok(())
     } else {
-        invalid_locations.sort();
         let invalid_locations = invalid_locations
             .into_iter()
             .map(|l| l.to_string())
@@ -65,21 +293,317 @@ fn validate_ast(ast: &[SourceLine]) -> Result<()> {
     }
 }
 
+// The locations in more than one line, found by comparing each range of
+// locations with the furthest reach of the ranges that start before it.
+fn overlapping_locations(ast: &[SourceLine]) -> Vec<Location> {
+    let mut ranges = ast
+        .iter()
+        .filter_map(|line| {
+            line.location()
+                .map(|location| (location, location.saturating_add(line.size())))
+        })
+        .filter(|(start, end)| start < end)
+        .collect::<Vec<_>>();
+    ranges.sort();
+
+    let mut locations = BTreeSet::new();
+    let mut reach = 0;
+    for (start, end) in ranges {
+        locations.extend(start..end.min(reach));
+        reach = reach.max(end);
+    }
+    locations.into_iter().collect()
+}
+
+// The errors of each of the validations, reported together.
+fn all_of(results: Vec<Result<()>>) -> Result<()> {
+    let errors = results
+        .into_iter()
+        .filter_map(Result::err)
+        .flat_map(|error| match error {
+            Error::FailedToAssemble(errors) => errors,
+            error => vec![error.to_string()],
+        })
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::FailedToAssemble(errors))
+    }
+}
+
+fn validate_operands(ast: &[SourceLine], memory_size: usize) -> Result<()> {
+    let errors = ast
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            operand_errors(line, memory_size)
+                .into_iter()
+                .map(move |error| format!("Line {}: {}", position(i, line), error))
+        })
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::FailedToAssemble(errors))
+    }
+}
+
+// Locations and addresses must be in the memory the program is assembled for.
+fn operand_errors(line: &SourceLine, memory_size: usize) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    if let Some(location) = line.location() {
+        let last = location.saturating_add(line.size().saturating_sub(1));
+        if last >= memory_size {
+            errors.push(format!("location {} out of range", last));
+        }
+    }
+
+    if let Some(Directive::Equ(value)) = line.directive() {
+        if *value >= memory_size {
+            errors.push(format!("value {} out of range", value));
+        }
+    }
+
+    if let Some(SourceWord::PWord(pword)) = line.source_program_word() {
+        let accumulator = pword.accumulator().as_usize();
+        if Accumulator::try_from(accumulator).is_err() {
+            errors.push(format!("accumulator {} out of range", accumulator));
+        }
+
+        if let StoreOperand::AddressOperand(operand) = pword.store_operand() {
+            if let Some(index) = operand.index() {
+                if IndexRegister::try_from(index).is_err() {
+                    errors.push(format!("index register {} out of range", index));
+                }
+            }
+
+            if let Address::NumericAddress(address) = operand_address(operand) {
+                errors.extend(address_error(pword.mnemonic(), address, memory_size));
+            }
+        }
+    }
+
+    errors
+}
+
+// Exported identifiers must be defined by the module, and imported ones must
+// not be. Imports can only be resolved by linking modules.
+fn validate_externals(ast: &[SourceLine], assembly: &Assembly, is_module: bool) -> Result<()> {
+    let errors = ast
+        .iter()
+        .enumerate()
+        .flat_map(|(i, line)| {
+            let errors = match line.directive() {
+                Some(Directive::Export(identifiers)) => identifiers
+                    .iter()
+                    .filter(|identifier| assembly.location(identifier).is_none())
+                    .map(|identifier| format!("exported identifier {} is not defined", identifier))
+                    .collect(),
+                Some(Directive::Import(identifiers)) => identifiers
+                    .iter()
+                    .filter_map(|identifier| {
+                        if assembly.location(identifier).is_some() {
+                            Some(format!(
+                                "imported identifier {} is also defined",
+                                identifier
+                            ))
+                        } else if !is_module {
+                            Some(format!(
+                                "imported identifier {} needs modules to be linked",
+                                identifier
+                            ))
+                        } else {
+                            None
+                        }
+                    })
+                    .collect(),
+                _ => Vec::new(),
+            };
+            errors
+                .into_iter()
+                .map(move |error| format!("Line {}: {}", position(i, line), error))
+        })
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::FailedToAssemble(errors))
+    }
+}
+
+// Identifiers and expressions are resolved once every identifier has been given
+// a location, and are then checked as numeric addresses are. Each error is placed
+// by the given function, such as by the line of the word at the location.
+pub(super) fn validate_resolved_operands(
+    assembly: &Assembly,
+    memory_size: usize,
+    place: impl Fn(Location) -> String,
+) -> Result<()> {
+    let errors = assembly
+        .resolved_operands()
+        .into_iter()
+        .filter_map(|(location, mnemonic, address)| {
+            let error = match assembly.evaluate(&address) {
+                None => Some(format!("address {} cannot be evaluated", address)),
+                Some(value) => usize::try_from(value)
+                    .map_or(Some(format!("address {} out of range", value)), |value| {
+                        address_error(mnemonic, value, memory_size)
+                    })
+                    .map(|error| format!("{}, from {}", error, address)),
+            };
+            error.map(|error| format!("{}: {}", place(location), error))
+        })
+        .collect::<Vec<_>>();
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(Error::FailedToAssemble(errors))
+    }
+}
+
+fn operand_address(operand: AddressOperand) -> Address {
+    match operand.address() {
+        SimpleAddressOperand::DirectAddress(address) => address,
+        SimpleAddressOperand::IndirectAddress(address) => address,
+    }
+}
+
+// Library routines take their accumulator in the address part of the
+// instruction, except READER and PUNCH, which take a device: 0 for the input
+// or output device, and 1 for paper tape.
+pub(super) fn address_error(
+    mnemonic: Mnemonic,
+    address: usize,
+    memory_size: usize,
+) -> Option<String> {
+    if matches!(mnemonic, Mnemonic::READER | Mnemonic::PUNCH) && address > 1 {
+        Some(format!("device {} is not 0 or 1", address))
+    } else if mnemonic.is_library_routine() && Accumulator::try_from(address).is_err() {
+        Some(format!("accumulator {} out of range", address))
+    } else if address >= memory_size {
+        Some(format!("address {} out of range", address))
+    } else {
+        None
+    }
+}
+
+// Each undefined identifier, in order of first use, with the lines that use it.
+fn undefined_identifier_uses(
+    ast: &[SourceLine],
+    assembly: &Assembly,
+) -> Vec<(Identifier, Vec<Position>)> {
+    let mut uses: Vec<(Identifier, Vec<Position>)> = Vec::new();
+    ast.iter()
+        .enumerate()
+        .filter_map(|(i, line)| match line.source_program_word() {
+            Some(SourceWord::PWord(pword)) => Some((position(i, line), pword.store_operand())),
+            _ => None,
+        })
+        .flat_map(|(line, operand)| match operand {
+            StoreOperand::AddressOperand(operand) => operand_address(operand)
+                .identifiers()
+                .into_iter()
+                .map(|identifier| (identifier, line.clone()))
+                .collect(),
+            _ => Vec::new(),
+        })
+        .filter(|(identifier, _)| {
+            assembly.location(identifier).is_none() && !assembly.imports().contains(identifier)
+        })
+        .for_each(|(identifier, line)| {
+            match uses.iter_mut().find(|(used, _)| *used == identifier) {
+                Some((_, lines)) => lines.push(line),
+                None => uses.push((identifier, vec![line])),
+            }
+        });
+    uses
+}
+
+// Lines are numbered from one, unless they have been numbered by the source.
+pub(super) fn position(i: usize, line: &SourceLine) -> Position {
+    line.position().cloned().unwrap_or(Position::new(i + 1))
+}
+
+fn join_lines(lines: &[Position]) -> String {
+    lines
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Text is expanded into consecutive S-words.
 fn generate_code(ast: &[SourceLine]) -> Code {
     ast.iter()
-        .filter_map(|line| {
-            line.source_program_word()
-                .map(|spw| (*line.location(), spw.clone()))
+        .filter_map(|line| line.location().map(|location| (location, line)))
+        .flat_map(|(location, line)| {
+            let words = match (line.source_program_word(), line.directive()) {
+                (Some(spw), _) => vec![spw.clone()],
+                (None, Some(directive)) => directive
+                    .text_words()
+                    .into_iter()
+                    .map(SourceWord::SWord)
+                    .collect(),
+                (None, None) => Vec::new(),
+            };
+            words
+                .into_iter()
+                .enumerate()
+                .map(move |(i, word)| (location + i, word))
         })
         .collect::<Code>()
 }
 
+// A label names its location, or the value given by EQU.
 fn generate_symbol_table(ast: &[SourceLine]) -> Symbols {
     ast.iter()
-        .filter_map(|line| line.label().name().map(|name| (name, *line.location())))
+        .filter_map(|line| {
+            let name = line.label().name()?;
+            match line.directive() {
+                Some(Directive::Equ(value)) => Some((name, *value)),
+                _ => line.location().map(|location| (name, location)),
+            }
+        })
         .collect::<Symbols>()
 }
 
+// The identifiers named by EXPORT and by IMPORT directives.
+fn externals(ast: &[SourceLine]) -> (Vec<Identifier>, Vec<Identifier>) {
+    ast.iter().fold(
+        (Vec::new(), Vec::new()),
+        |(mut exports, mut imports), line| {
+            match line.directive() {
+                Some(Directive::Export(identifiers)) => exports.extend(identifiers.clone()),
+                Some(Directive::Import(identifiers)) => imports.extend(identifiers.clone()),
+                _ => {}
+            }
+            (exports, imports)
+        },
+    )
+}
+
+fn constants(ast: &[SourceLine]) -> Vec<Identifier> {
+    ast.iter()
+        .filter(|line| matches!(line.directive(), Some(Directive::Equ(_))))
+        .filter_map(|line| line.label().name())
+        .collect()
+}
+
+fn reserved_blocks(ast: &[SourceLine]) -> Vec<(Location, usize)> {
+    ast.iter()
+        .filter_map(|line| match line.directive() {
+            Some(Directive::Reserve(size)) => line.location().map(|location| (location, *size)),
+            _ => None,
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -92,7 +616,7 @@ mod test {
         input
             .to_string()
             .lines()
-            .map(|l| Parser::parse_line(l))
+            .map(Parser::parse_line)
             .filter_map(Result::ok)
             .collect::<SourceProgram>()
     }
@@ -156,8 +680,8 @@ mod test {
 "#;
         let program = parse(program);
         let assembly = Assembler::assemble(&program).unwrap();
-        assert_eq!(assembly.location("LABEL1".into()), 1.into());
-        assert_eq!(assembly.location("LABEL2".into()), 2.into());
+        assert_eq!(assembly.location("LABEL1"), 1.into());
+        assert_eq!(assembly.location("LABEL2"), 2.into());
     }
 
     #[test]
@@ -179,6 +703,472 @@ mod test {
         );
     }
 
+    #[test]
+    fn fails_when_operands_out_of_range() {
+        let program = r#"
+0001    TAKE    1, 1024
+0002    TAKE    1, 20(12)
+0003    READ    9
+0004    PRINT   7
+1024    STOP
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program).err().unwrap();
+        assert_eq!(
+            result,
+            Error::FailedToAssemble(vec![
+                "Line 2: address 1024 out of range".into(),
+                "Line 3: index register 12 out of range".into(),
+                "Line 4: accumulator 9 out of range".into(),
+                "Line 6: location 1024 out of range".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn fails_when_resolved_operands_out_of_range() {
+        let program = r#"
+        CAPN    NL
+        CAPN    1
+        STOP
+0025    NL:     " "
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program).err().unwrap();
+        assert_eq!(
+            result,
+            Error::FailedToAssemble(vec!["Line 2: accumulator 25 out of range, from NL".into()])
+        );
+    }
+
+    #[test]
+    fn fails_when_device_is_not_0_or_1() {
+        let program = r#"
+0100    READER  1
+0101    PUNCH   3
+0102    STOP
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program).err().unwrap();
+        assert_eq!(
+            result,
+            Error::FailedToAssemble(vec!["Line 3: device 3 is not 0 or 1".into(),])
+        );
+    }
+
+    #[test]
+    fn will_allocate_undefined_identifiers_by_default() {
+        let program = r#"
+0001    TAKE    1, COUNT
+0002    PUT     1, COUNT
+"#;
+        let program = parse(program);
+        let assembly = Assembler::assemble(&program).unwrap();
+        assert!(assembly.warnings().is_empty());
+    }
+
+    #[test]
+    fn will_allocate_undefined_identifiers_in_order_of_first_use() {
+        let program = r#"
+0001    TAKE    1, COUNT
+0002    ADD     1, TOTAL
+0003    PUT     1, COUNT
+0004    TAKE    2, LIMIT
+0005    ADD     2, +1
+0006    PUT     2, TOTAL
+"#;
+        let program = parse(program);
+        let assembly = Assembler::assemble(&program)
+            .unwrap()
+            .allocate_storage(1024)
+            .unwrap();
+        assert_eq!(
+            assembly.storage_map()[1..],
+            vec![
+                "1020       literal +1 used at 0005",
+                "1021       identifier LIMIT",
+                "1022       identifier TOTAL",
+                "1023       identifier COUNT",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_warn_of_undefined_identifiers() {
+        let program = r#"
+0001    TAKE    1, COUNT
+0002    PUT     1, TOTAL
+0003    PUT     1, COUNT
+0004    DONE:   STOP
+0005    JUMP    DONE
+"#;
+        let program = parse(program);
+        let assembly = Assembler::default()
+            .with_undefined_identifiers(UndefinedIdentifiers::Warn)
+            .with_memory_size(512)
+            .build(&program)
+            .unwrap();
+        assert_eq!(
+            assembly.warnings(),
+            vec![
+                "Identifier COUNT allocated at 0511, used on lines 2, 4".to_string(),
+                "Identifier TOTAL allocated at 0510, used on lines 3".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn fails_on_undefined_identifiers_when_strict() {
+        let program = r#"
+0001    TAKE    1, COUNT
+0002    JUMP    DONR
+0003    DONE:   STOP
+"#;
+        let program = parse(program);
+        let result = Assembler::default()
+            .with_undefined_identifiers(UndefinedIdentifiers::Strict)
+            .build(&program)
+            .err()
+            .unwrap();
+        assert_eq!(
+            result,
+            Error::FailedToAssemble(vec![
+                "Undefined identifier COUNT, used on lines 2".into(),
+                "Undefined identifier DONR, used on lines 3".into(),
+            ])
+        );
+    }
+
+    #[test]
+    fn fails_when_allocated_storage_overlaps_program() {
+        let program = r#"
+0001    TAKE    1, COUNT
+0002    ADD     1, +1
+1023    +0
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program).err().unwrap();
+        assert_eq!(
+            result,
+            Error::FailedToAssemble(vec![
+                "Storage for identifier COUNT allocated at 1023 overlaps a program word".into()
+            ])
+        );
+    }
+
+    #[test]
+    fn will_map_program_and_allocated_storage() {
+        let program = r#"
+0001    TAKE    1, COUNT
+0002    ADD     1, +1
+0003    STOP
+0010    "ABCD"
+"#;
+        let program = parse(program);
+        let assembly = Assembler::assemble(&program)
+            .unwrap()
+            .allocate_storage(1024)
+            .unwrap();
+        assert_eq!(
+            assembly.storage_map(),
+            vec![
+                "0001-0003  program",
+                "0010       program",
+                "1022       literal +1 used at 0002",
+                "1023       identifier COUNT",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_assemble_directives() {
+        let program = r#"
+SIZE:   EQU     3
+        ORIGIN  100
+START:  TAKE    1, SIZE
+        EXTRA   15          ; CAPN
+        TEXT    "HELLO"
+        STOP
+BUF:    RESERVE 3
+NEXT:   STOP
+"#;
+        let program = parse(program);
+        let assembly = Assembler::assemble(&program).unwrap();
+        assert_eq!(assembly.location("SIZE"), Some(3));
+        assert_eq!(assembly.location("START"), Some(100));
+        assert_eq!(
+            assembly.content(102),
+            Some(SourceWord::SWord("HELL".into()))
+        );
+        assert_eq!(
+            assembly.content(103),
+            Some(SourceWord::SWord("O   ".into()))
+        );
+        assert!(matches!(assembly.content(104), Some(SourceWord::PWord(_))));
+        assert_eq!(assembly.location("BUF"), Some(105));
+        assert_eq!(assembly.content(105), None);
+        assert_eq!(assembly.location("NEXT"), Some(108));
+
+        let assembly = assembly.allocate_storage(1024).unwrap();
+        assert_eq!(
+            assembly.storage_map(),
+            vec![
+                "0100-0104  program",
+                "0105-0107  reserved",
+                "0108       program"
+            ]
+        );
+    }
+
+    #[test]
+    fn fails_with_duplicate_labels_and_bad_operands_together() {
+        let program = r#"
+0100    LOOP:   TAKE    1, 2000
+0101    LOOP:   JUMP    0, LOOP
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Multiple definitions: locations: \"\", labels: \"LOOP:\"".into(),
+                "Line 2: address 2000 out of range".into(),
+            ]))
+        );
+    }
+
+    #[test]
+    fn fails_when_locations_and_addresses_are_beyond_memory_size() {
+        let program = r#"
+0100    TAKE    1, 600
+0600    +1
+        STOP
+"#;
+        let program = parse(program);
+        let result = Assembler::default().with_memory_size(512).build(&program);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Line 2: address 600 out of range".into(),
+                "Line 3: location 600 out of range".into(),
+                "Line 4: location 601 out of range".into(),
+            ]))
+        );
+    }
+
+    #[test]
+    fn fails_when_directives_overlap_or_out_of_range() {
+        let program = r#"
+        ORIGIN  100
+BUF:    RESERVE 2
+0101    STOP
+BIG:    EQU     2000
+        ORIGIN  1022
+        TEXT    "ABCDEFGHI"
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Multiple definitions: locations: \"101\", labels: \"\"".into(),
+                "Line 5: value 2000 out of range".into(),
+                "Line 7: location 1024 out of range".into(),
+            ]))
+        );
+
+        let program = r#"
+        ORIGIN  100
+BIG:    EQU     2000
+        ORIGIN  1022
+        TEXT    "ABCDEFGHI"
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Line 3: value 2000 out of range".into(),
+                "Line 5: location 1024 out of range".into(),
+            ]))
+        );
+    }
+
+    #[test]
+    fn fails_when_free_form_line_has_location_only() {
+        let program = r#"
+ANSWER:
+        42    ; the answer
+        +42   ; the answer
+        STOP
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Line 3: location 42 has no word; write an I-word with a sign, such as +42".into()
+            ]))
+        );
+
+        let program = r#"
+0008    ;"SQRT"
+0009    STOP
+"#;
+        let program = parse(program);
+        assert!(Assembler::assemble(&program).is_ok());
+    }
+
+    #[test]
+    fn fails_when_reserved_block_runs_past_memory() {
+        let program = r#"
+BUF:    RESERVE 99999999999
+        STOP
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Line 2: RESERVE 99999999999 at 0008 runs past the end of memory".into()
+            ]))
+        );
+
+        let program = r#"
+        ORIGIN  100
+BUF:    RESERVE 900
+0500    RESERVE 10
+0900    STOP
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![format!(
+                "Multiple definitions: locations: \"{}\", labels: \"\"",
+                (500..510)
+                    .chain(std::iter::once(900))
+                    .map(|l| l.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            )]))
+        );
+    }
+
+    #[test]
+    fn will_resolve_address_expressions() {
+        let program = r#"
+0100    TAKE    1, TABLE+2
+0101    TAKE    2, END-TABLE
+0102    TAKE    3, *TABLE+2*3-1(1)
+0103    STOP
+TABLE:  RESERVE 10
+END:    STOP
+"#;
+        let program = parse(program);
+        let assembly = Assembler::assemble(&program).unwrap();
+        let code = assembly.linked_code();
+        let address = |location: usize| match &code[&location] {
+            SourceWord::PWord(pword) => {
+                assembly.address_used_by_store_operand(pword.store_operand())
+            }
+            _ => unreachable!(),
+        };
+        assert_eq!(address(100), 106);
+        assert_eq!(address(101), 10);
+        assert_eq!(address(102), 109);
+    }
+
+    #[test]
+    fn fails_when_address_expressions_out_of_range() {
+        let program = r#"
+0100    TAKE    1, TABLE+1000
+0101    TAKE    1, TABLE-200
+0102    TAKE    1, TABLE/0
+0103    SQRT    TABLE-96
+TABLE:  STOP
+"#;
+        let program = parse(program);
+        let result = Assembler::assemble(&program);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Line 2: address 1104 out of range, from TABLE+1000".into(),
+                "Line 3: address -96 out of range, from TABLE-200".into(),
+                "Line 4: address TABLE/0 cannot be evaluated".into(),
+                "Line 5: accumulator 8 out of range, from TABLE-96".into(),
+            ]))
+        );
+    }
+
+    #[test]
+    fn will_share_identical_literals() {
+        let program = r#"
+0001    TAKE    1, +1
+0002    ADD     1, +1
+0003    ADD     1, +1.0
+0004    TAKE    2, "    "
+0005    TAKE    3, "    "
+"#;
+        let program = parse(program);
+        let assembly = Assembler::assemble(&program)
+            .unwrap()
+            .allocate_storage(1024)
+            .unwrap();
+        assert_eq!(
+            assembly.literal_pool(),
+            vec![
+                "1021  literal \"    \" used at 0004, 0005",
+                "1022  literal +1.0 used at 0003",
+                "1023  literal +1 used at 0001, 0002",
+            ]
+        );
+        assert_eq!(assembly.literal_location(2), Some(1023));
+    }
+
+    #[test]
+    fn will_allocate_literal_per_use() {
+        let program = r#"
+0001    TAKE    1, +1
+0002    ADD     1, +1
+"#;
+        let program = parse(program);
+        let assembly = Assembler::default()
+            .with_literal_pool(LiteralPool::PerUse)
+            .build(&program)
+            .unwrap()
+            .allocate_storage(1024)
+            .unwrap();
+        assert_eq!(
+            assembly.literal_pool(),
+            vec![
+                "1022  literal +1 used at 0002",
+                "1023  literal +1 used at 0001",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_place_lines_without_location_using_location_counter() {
+        let program = r#"
+        "TEXT"
+        ORIGIN 100
+START:  TAKE    1, 20
+        ; Comment
+LOOP:
+        JUMP    LOOP
+0200    STOP
+        +1
+"#;
+        let program = parse(program);
+        let assembly = Assembler::assemble(&program).unwrap();
+        assert_eq!(assembly.content(8), Some(SourceWord::SWord("TEXT".into())));
+        assert_eq!(assembly.location("START"), Some(100));
+        assert_eq!(assembly.location("LOOP"), Some(101));
+        assert!(matches!(assembly.content(101), Some(SourceWord::PWord(_))));
+        assert_eq!(assembly.content(201), Some(SourceWord::IWord(1)));
+    }
+
     #[test]
     fn will_create_intermediate_code_representation() {
         let program = r#"
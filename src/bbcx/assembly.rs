@@ -1,10 +1,13 @@
 use super::ast::{
-    Address as AstAddress, AddressOperand, Identifier, Location as AstLocation, PWord as AstPWord,
+    Address as AstAddress, AddressOperand, ConstOperand as AstConstOperand, Identifier,
+    Location as AstLocation, Mnemonic, PWord as AstPWord,
     SimpleAddressOperand as AstSimpleAddressOperand, SourceWord as AstSourceWord,
     StoreOperand as AstStoreOperand,
 };
 
-use std::collections::HashMap;
+use crate::result::{Error, Result};
+
+use std::collections::{BTreeMap, HashMap};
 
 pub type Location = AstLocation;
 pub type Content = AstSourceWord;
@@ -12,20 +15,223 @@ pub type Content = AstSourceWord;
 pub type Code = HashMap<Location, Content>;
 pub type Symbols = HashMap<Identifier, Location>;
 
+/// A word of storage allocated by the assembler, rather than placed by the program.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Allocation {
+    Identifier(Identifier),
+    Literal(String, Vec<Location>),
+}
+
+impl std::fmt::Display for Allocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Allocation::Identifier(identifier) => write!(f, "identifier {}", identifier),
+            Allocation::Literal(literal, locations) => {
+                let locations = locations
+                    .iter()
+                    .map(|location| format!("{:04}", location))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                write!(f, "literal {} used at {}", literal, locations)
+            }
+        }
+    }
+}
+
+pub type Storage = BTreeMap<Location, Allocation>;
+
+/// How literal operands are given storage. Identical typed literals can share a
+/// word in the pool or, as in the original assembler, each use can have its own.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LiteralPool {
+    #[default]
+    Shared,
+    PerUse,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct Assembly {
     code: Code,
     symbols: Symbols,
+    literals: HashMap<Location, Location>,
+    storage: Storage,
+    reserved: Vec<(Location, usize)>,
+    constants: Vec<Identifier>,
+    exports: Vec<Identifier>,
+    imports: Vec<Identifier>,
+    literal_pool: LiteralPool,
+    warnings: Vec<String>,
 }
 
 impl Assembly {
     pub fn new(code: &Code, symbols: &Symbols) -> Self {
         let code = code.clone();
         let symbols = symbols.clone();
-        Self { code, symbols }
+        Self {
+            code,
+            symbols,
+            literals: HashMap::new(),
+            storage: Storage::new(),
+            reserved: Vec::new(),
+            constants: Vec::new(),
+            exports: Vec::new(),
+            imports: Vec::new(),
+            literal_pool: LiteralPool::default(),
+            warnings: Vec::new(),
+        }
+    }
+
+    pub fn with_literal_pool(mut self, literal_pool: LiteralPool) -> Self {
+        self.literal_pool = literal_pool;
+        self
+    }
+
+    /// Blocks of words, given as their first location and size, that are set
+    /// aside by the program without content.
+    pub fn with_reserved(mut self, reserved: Vec<(Location, usize)>) -> Self {
+        self.reserved = reserved;
+        self
+    }
+
+    fn is_reserved(&self, location: Location) -> bool {
+        self.reserved
+            .iter()
+            .any(|&(first, size)| (first..first + size).contains(&location))
+    }
+
+    /// The identifiers whose values are given by EQU rather than by a location, so
+    /// that they are not moved when the assembly is relocated.
+    pub fn with_constants(mut self, constants: Vec<Identifier>) -> Self {
+        self.constants = constants;
+        self
+    }
+
+    /// The identifiers defined for use by other modules, and those used from them.
+    pub fn with_externals(mut self, exports: Vec<Identifier>, imports: Vec<Identifier>) -> Self {
+        self.exports = exports;
+        self.imports = imports;
+        self
+    }
+
+    pub fn exports(&self) -> &[Identifier] {
+        &self.exports
+    }
+
+    pub fn imports(&self) -> &[Identifier] {
+        &self.imports
+    }
+
+    /// The first and last locations of the program words and reserved blocks. A
+    /// label can name a location before the first of them, so the extent starts at
+    /// the lowest label, so that relocation moves no label below zero.
+    pub fn extent(&self) -> Option<(Location, Location)> {
+        let locations = self.code.keys().copied().chain(
+            self.reserved
+                .iter()
+                .filter(|(_, size)| *size > 0)
+                .flat_map(|&(first, size)| [first, first + size - 1]),
+        );
+        let last = locations.clone().max()?;
+        let labels = self
+            .symbols
+            .iter()
+            .filter(|(identifier, _)| !self.constants.contains(identifier))
+            .map(|(_, location)| *location);
+        let first = locations.chain(labels).min()?;
+        Some((first, last))
+    }
+
+    /// Move the program words, reserved blocks and labels so that those at `from`
+    /// are at `to`. Constants and numeric addresses are not moved.
+    pub fn relocated(mut self, from: Location, to: Location) -> Self {
+        let relocate = |location: Location| location - from + to;
+        self.code = self
+            .code
+            .into_iter()
+            .map(|(location, content)| (relocate(location), content))
+            .collect();
+        self.symbols = self
+            .symbols
+            .into_iter()
+            .map(
+                |(identifier, location)| match self.constants.contains(&identifier) {
+                    true => (identifier, location),
+                    false => (identifier, relocate(location)),
+                },
+            )
+            .collect();
+        self.reserved = self
+            .reserved
+            .into_iter()
+            .map(|(first, size)| (relocate(first), size))
+            .collect();
+        self
+    }
+
+    /// Prefix the identifiers that are neither exported nor imported with the name
+    /// of the module, so that they cannot clash with those of other modules.
+    pub fn qualified(mut self, module: &str) -> Self {
+        let externals = [self.exports.as_slice(), self.imports.as_slice()].concat();
+        let rename = |identifier: &Identifier| match externals.contains(identifier) {
+            true => identifier.clone(),
+            false => format!("{}.{}", module, identifier),
+        };
+        self.code = self
+            .code
+            .iter()
+            .map(|(location, content)| (*location, renamed_content(content, &rename)))
+            .collect();
+        self.symbols = self
+            .symbols
+            .iter()
+            .map(|(identifier, location)| (rename(identifier), *location))
+            .collect();
+        self.constants = self.constants.iter().map(rename).collect();
+        self
+    }
+
+    /// Add the program words, symbols and reserved blocks of another module. Neither
+    /// assembly can have had storage allocated.
+    pub fn merged(mut self, module: Assembly) -> Self {
+        self.code.extend(module.code);
+        self.symbols.extend(module.symbols);
+        self.reserved.extend(module.reserved);
+        self.constants.extend(module.constants);
+        self.exports.extend(module.exports);
+        self.imports.extend(module.imports);
+        self.warnings.extend(module.warnings);
+        self
+    }
+
+    /// The P-words whose operand is an identifier or an address expression, in
+    /// location order.
+    pub fn resolved_operands(&self) -> Vec<(Location, Mnemonic, AstAddress)> {
+        self.code_iter()
+            .filter_map(|(location, content)| match content {
+                AstSourceWord::PWord(pword) => match pword.store_operand() {
+                    AstStoreOperand::AddressOperand(operand) => match operand.address() {
+                        AstSimpleAddressOperand::DirectAddress(address)
+                        | AstSimpleAddressOperand::IndirectAddress(address) => {
+                            Some((*location, pword.mnemonic(), address))
+                        }
+                    },
+                    _ => None,
+                },
+                _ => None,
+            })
+            .filter(|(_, _, address)| !matches!(address, AstAddress::NumericAddress(_)))
+            .collect()
+    }
+
+    pub fn with_warnings(mut self, warnings: Vec<String>) -> Self {
+        self.warnings = warnings;
+        self
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
     }
 
-    #[cfg(test)]
     pub fn content(&self, location: Location) -> Option<Content> {
         self.code.get(&location).cloned()
     }
@@ -114,10 +320,28 @@ impl Assembly {
     fn linked_address(&self, address: AstAddress) -> AstAddress {
         match address {
             AstAddress::Identifier(id) => AstAddress::NumericAddress(self.symbols[&id]),
+            AstAddress::Expression(..) => {
+                let value = self
+                    .evaluate(&address)
+                    .expect("Address expression validated by the assembler");
+                AstAddress::NumericAddress(value as Location)
+            }
             other => other,
         }
     }
 
+    /// The value of an address, once every identifier it uses has a location.
+    /// Undefined identifiers, overflow and division by zero give no value.
+    pub fn evaluate(&self, address: &AstAddress) -> Option<i64> {
+        match address {
+            AstAddress::Identifier(id) => self.location(id).map(|location| location as i64),
+            AstAddress::NumericAddress(a) => Some(*a as i64),
+            AstAddress::Expression(left, operator, right) => {
+                operator.apply(self.evaluate(left)?, self.evaluate(right)?)
+            }
+        }
+    }
+
     fn address_used_by(&self, address: AstAddress) -> Location {
         match address {
             AstAddress::NumericAddress(a) => a,
@@ -125,20 +349,176 @@ impl Assembly {
         }
     }
 
+    pub fn symbols(&self) -> &Symbols {
+        &self.symbols
+    }
+
     pub fn location(&self, label: &str) -> Option<Location> {
         self.symbols.get(label).copied()
     }
 
-    pub fn allocate_storage_locations(mut self) -> Self {
-        let undefined_symbols = self.undefined_symbols();
-        let mut store_location: usize = 1024;
-        undefined_symbols.into_iter().for_each(|identifier| {
-            store_location -= 1;
-            self.symbols.insert(identifier, store_location);
-        });
-        self
+    /// Allocate a word for each undefined identifier, in order of first use,
+    /// followed by a word for each literal operand, downwards from the top of
+    /// memory. Allocation fails if any allocated word is also placed or reserved
+    /// by the program. Storage is only allocated once.
+    pub fn allocate_storage(mut self, memory_size: usize) -> Result<Self> {
+        if !self.storage.is_empty() {
+            return Ok(self);
+        }
+
+        let identifiers = self
+            .undefined_symbols()
+            .into_iter()
+            .map(Allocation::Identifier);
+        let literals = self.literal_uses();
+
+        let mut errors = Vec::new();
+        let mut next_location = memory_size;
+        for allocation in identifiers.chain(literals) {
+            if next_location == 0 {
+                errors.push(format!("No storage left for {}", allocation));
+                break;
+            }
+            next_location -= 1;
+
+            if self.code.contains_key(&next_location) {
+                errors.push(format!(
+                    "Storage for {} allocated at {:04} overlaps a program word",
+                    allocation, next_location
+                ));
+            } else if self.is_reserved(next_location) {
+                errors.push(format!(
+                    "Storage for {} allocated at {:04} overlaps a reserved block",
+                    allocation, next_location
+                ));
+            }
+
+            match &allocation {
+                Allocation::Identifier(identifier) => {
+                    self.symbols.insert(identifier.clone(), next_location);
+                }
+                Allocation::Literal(_, locations) => {
+                    locations.iter().for_each(|location| {
+                        self.literals.insert(*location, next_location);
+                    });
+                }
+            }
+            self.storage.insert(next_location, allocation);
+        }
+
+        if errors.is_empty() {
+            Ok(self)
+        } else {
+            Err(Error::FailedToAssemble(errors))
+        }
     }
 
+    // The literal operands, each with the locations that use it, in order of first use.
+    fn literal_uses(&self) -> Vec<Allocation> {
+        let mut uses: Vec<(AstConstOperand, Vec<Location>)> = Vec::new();
+        self.code_iter()
+            .filter_map(|(location, content)| match content {
+                AstSourceWord::PWord(pword) => match pword.store_operand() {
+                    AstStoreOperand::ConstOperand(literal) => Some((literal, *location)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .for_each(|(literal, location)| {
+                let shared = match self.literal_pool {
+                    LiteralPool::Shared => uses.iter_mut().find(|(used, _)| *used == literal),
+                    LiteralPool::PerUse => None,
+                };
+                match shared {
+                    Some((_, locations)) => locations.push(location),
+                    None => uses.push((literal, vec![location])),
+                }
+            });
+        uses.into_iter()
+            .map(|(literal, locations)| Allocation::Literal(literal.to_string(), locations))
+            .collect()
+    }
+
+    /// The words allocated by the assembler, by location.
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    /// Whether an identifier is given its value by EQU.
+    pub fn is_constant(&self, identifier: &str) -> bool {
+        self.constants.iter().any(|constant| constant == identifier)
+    }
+
+    /// The allocated literals, in location order.
+    #[cfg(test)]
+    pub fn literal_pool(&self) -> Vec<String> {
+        self.storage
+            .iter()
+            .filter(|(_, allocation)| matches!(allocation, Allocation::Literal(..)))
+            .map(|(location, allocation)| format!("{:04}  {}", location, allocation))
+            .collect()
+    }
+
+    pub fn literal_location(&self, location: Location) -> Option<Location> {
+        self.literals.get(&location).copied()
+    }
+
+    /// The program words, as contiguous ranges, the reserved blocks and the
+    /// allocated words in location order.
+    pub fn storage_map(&self) -> Vec<String> {
+        self.storage_map_entries()
+            .into_iter()
+            .map(|(_, range, description)| format!("{:<11}{}", range, description))
+            .collect()
+    }
+
+    /// The entries of the storage map, each with its first location, its range of
+    /// locations and a description of what they hold.
+    pub fn storage_map_entries(&self) -> Vec<(Location, String, String)> {
+        let mut locations = Vec::from_iter(self.code.keys().copied());
+        locations.sort();
+
+        let mut ranges: Vec<(Location, Location)> = Vec::new();
+        for location in locations {
+            match ranges.last_mut() {
+                Some((_, last)) if *last + 1 == location => *last = location,
+                _ => ranges.push((location, location)),
+            }
+        }
+
+        let format_range = |first: Location, last: Location| {
+            if first == last {
+                format!("{:04}", first)
+            } else {
+                format!("{:04}-{:04}", first, last)
+            }
+        };
+
+        let mut map = ranges
+            .into_iter()
+            .map(|(first, last)| (first, format_range(first, last), "program".to_string()))
+            .chain(
+                self.reserved
+                    .iter()
+                    .filter(|(_, size)| *size > 0)
+                    .map(|&(first, size)| {
+                        let range = format_range(first, first + size - 1);
+                        (first, range, "reserved".to_string())
+                    }),
+            )
+            .chain(self.storage.iter().map(|(location, allocation)| {
+                (
+                    *location,
+                    format!("{:04}", location),
+                    allocation.to_string(),
+                )
+            }))
+            .collect::<Vec<_>>();
+        map.sort_by_key(|(location, _, _)| *location);
+        map
+    }
+
+    // Each undefined identifier once, in location order of its first use.
     fn undefined_symbols(&self) -> Vec<Identifier> {
         self.code_iter()
             .filter_map(|(_, content)| match content {
@@ -154,15 +534,14 @@ impl Assembly {
                 AstSimpleAddressOperand::DirectAddress(address) => address,
                 AstSimpleAddressOperand::IndirectAddress(address) => address,
             })
-            .filter_map(|address| match address {
-                AstAddress::Identifier(identifer) => Some(identifer),
-                AstAddress::NumericAddress(_) => None,
-            })
-            .filter_map(|identifier| match self.location(&identifier) {
-                Some(_) => None,
-                None => Some(identifier),
+            .flat_map(|address| address.identifiers())
+            .filter(|identifier| self.location(identifier).is_none())
+            .fold(Vec::new(), |mut identifiers, identifier| {
+                if !identifiers.contains(&identifier) {
+                    identifiers.push(identifier);
+                }
+                identifiers
             })
-            .collect::<Vec<_>>()
     }
 
     pub fn first_pword_location(&self) -> Option<Location> {
@@ -173,3 +552,27 @@ impl Assembly {
             })
     }
 }
+
+fn renamed_content(content: &Content, rename: &impl Fn(&Identifier) -> Identifier) -> Content {
+    match content {
+        Content::PWord(pword) => match pword.store_operand() {
+            AstStoreOperand::AddressOperand(operand) => {
+                let address = match operand.address() {
+                    AstSimpleAddressOperand::DirectAddress(address) => {
+                        AstSimpleAddressOperand::DirectAddress(address.renamed(rename))
+                    }
+                    AstSimpleAddressOperand::IndirectAddress(address) => {
+                        AstSimpleAddressOperand::IndirectAddress(address.renamed(rename))
+                    }
+                };
+                Content::PWord(AstPWord::new(
+                    pword.mnemonic(),
+                    pword.accumulator(),
+                    AstStoreOperand::AddressOperand(AddressOperand::new(address, operand.index())),
+                ))
+            }
+            _ => content.clone(),
+        },
+        other => other.clone(),
+    }
+}
@@ -2,29 +2,89 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct SourceLine {
-    location: Location,
+    location: Option<Location>,
     label: Label,
     source_program_word: Option<SourceWord>,
+    directive: Option<Directive>,
     comment: Comment,
+    position: Option<Position>,
 }
 
 impl SourceLine {
+    #[cfg(test)]
     pub fn new(
         location: Location,
         label: Label,
         source_program_word: Option<SourceWord>,
         comment: Comment,
     ) -> Self {
+        Self::from_statement(
+            Some(location),
+            label,
+            source_program_word.map(Statement::SourceWord),
+            comment,
+        )
+    }
+
+    pub fn from_statement(
+        location: Option<Location>,
+        label: Label,
+        statement: Option<Statement>,
+        comment: Comment,
+    ) -> Self {
+        let (source_program_word, directive) = match statement {
+            Some(Statement::SourceWord(word)) => (Some(word), None),
+            Some(Statement::Directive(directive)) => (None, Some(directive)),
+            None => (None, None),
+        };
         Self {
             location,
             label,
             source_program_word,
+            directive,
             comment,
+            position: None,
         }
     }
 
-    pub fn location(&self) -> &Location {
-        &self.location
+    pub fn with_location(mut self, location: Location) -> Self {
+        self.location = Some(location);
+        self
+    }
+
+    /// The line without the location it was given in the source, such as when
+    /// the location is shown in the object code column of a listing.
+    pub fn without_location(mut self) -> Self {
+        self.location = None;
+        self
+    }
+
+    pub fn location(&self) -> Option<Location> {
+        self.location
+    }
+
+    /// The position of the source line that gave rise to the line, such as the
+    /// invocation of a macro that expanded to it.
+    pub fn with_position(mut self, position: Position) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub fn position(&self) -> Option<&Position> {
+        self.position.as_ref()
+    }
+
+    pub fn directive(&self) -> Option<&Directive> {
+        self.directive.as_ref()
+    }
+
+    /// The number of consecutive words occupied by the line.
+    pub fn size(&self) -> usize {
+        match (&self.source_program_word, &self.directive) {
+            (Some(_), _) => 1,
+            (None, Some(directive)) => directive.size(),
+            (None, None) => 0,
+        }
     }
 
     pub fn label(&self) -> &Label {
@@ -34,16 +94,23 @@ impl SourceLine {
     pub fn source_program_word(&self) -> Option<&SourceWord> {
         self.source_program_word.as_ref()
     }
+
+    pub fn comment(&self) -> &Comment {
+        &self.comment
+    }
 }
 
 impl std::fmt::Display for SourceLine {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
-        let location = format!("{:04}", self.location);
+        let location = self
+            .location
+            .map_or("".to_string(), |l| format!("{:04}", l));
         let label = format!("{:08}", self.label);
-        let source_program_word = self
-            .source_program_word
-            .clone()
-            .map_or("".to_string(), |w| w.to_string());
+        let source_program_word = match (&self.source_program_word, &self.directive) {
+            (Some(word), _) => word.to_string(),
+            (None, Some(directive)) => directive.to_string(),
+            (None, None) => "".to_string(),
+        };
         let comment = self.comment.to_string();
 
         write!(
@@ -56,6 +123,111 @@ impl std::fmt::Display for SourceLine {
 
 pub type Location = AddressRef;
 
+/// A line number in the program, or in a file it includes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Position {
+    line: usize,
+    file: Option<String>,
+}
+
+impl Position {
+    pub fn new(line: usize) -> Self {
+        Self { line, file: None }
+    }
+
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    pub fn is_included(&self) -> bool {
+        self.file.is_some()
+    }
+}
+
+impl std::fmt::Display for Position {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            None => write!(f, "{}", self.line),
+            Some(file) => write!(f, "{} of {}", self.line, file),
+        }
+    }
+}
+
+/// The part of a source line that is either placed in memory or directs the assembler.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    SourceWord(SourceWord),
+    Directive(Directive),
+}
+
+/// An instruction to the assembler, which does not itself occupy a word of memory.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Directive {
+    Origin(Location),
+    Equ(NumericAddress),
+    Reserve(usize),
+    Text(String),
+    Export(Vec<Identifier>),
+    Import(Vec<Identifier>),
+}
+
+impl Directive {
+    /// The number of consecutive words occupied by the directive.
+    pub fn size(&self) -> usize {
+        match self {
+            Directive::Origin(_)
+            | Directive::Equ(_)
+            | Directive::Export(_)
+            | Directive::Import(_) => 0,
+            Directive::Reserve(size) => *size,
+            Directive::Text(text) => text.len().div_ceil(Directive::TEXT_WORD_LENGTH),
+        }
+    }
+
+    /// The SWords holding the text, padded with spaces to fill the last word.
+    pub fn text_words(&self) -> Vec<SWord> {
+        match self {
+            Directive::Text(text) => text
+                .chars()
+                .collect::<Vec<_>>()
+                .chunks(Directive::TEXT_WORD_LENGTH)
+                .map(|chunk| {
+                    format!(
+                        "{:<width$}",
+                        String::from_iter(chunk),
+                        width = Directive::TEXT_WORD_LENGTH
+                    )
+                })
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    const TEXT_WORD_LENGTH: usize = 4;
+}
+
+impl std::fmt::Display for Directive {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        match self {
+            Directive::Origin(location) => write!(f, "{:<8}{}", "ORIGIN", location),
+            Directive::Equ(value) => write!(f, "{:<8}{}", "EQU", value),
+            Directive::Reserve(size) => write!(f, "{:<8}{}", "RESERVE", size),
+            Directive::Text(text) => write!(f, "{:<8}\"{}\"", "TEXT", text),
+            Directive::Export(identifiers) => {
+                write!(f, "{:<8}{}", "EXPORT", identifiers.join(", "))
+            }
+            Directive::Import(identifiers) => {
+                write!(f, "{:<8}{}", "IMPORT", identifiers.join(", "))
+            }
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct Label(Option<String>);
 
@@ -102,7 +274,7 @@ impl std::fmt::Display for SourceWord {
         match self {
             SourceWord::SWord(sword) => write!(f, "\"{}\"", sword),
             SourceWord::PWord(pword) => write!(f, "{}", pword),
-            SourceWord::FWord(fword) => write!(f, "{}", fword),
+            SourceWord::FWord(fword) => write!(f, "{}", float_text(format!("{}", fword))),
             SourceWord::IWord(iword) => write!(f, "{}", iword),
         }
     }
@@ -157,13 +329,16 @@ impl PWord {
 
 impl std::fmt::Display for PWord {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
-        write!(
-            f,
-            "{:<8}{:>2} {}",
-            self.mnemonic.to_string(),
-            self.accumulator.to_string(),
-            self.store_operand
-        )
+        match self.accumulator.0 {
+            Some(acc) => write!(
+                f,
+                "{:<8}{}, {}",
+                self.mnemonic.to_string(),
+                acc,
+                self.store_operand
+            ),
+            None => write!(f, "{:<8}{}", self.mnemonic.to_string(), self.store_operand),
+        }
     }
 }
 
@@ -197,6 +372,15 @@ impl std::fmt::Display for Acc {
 }
 
 pub type FWord = FloatType;
+
+// A whole number is written with a fraction, so that it is read back as an F-word.
+fn float_text(text: String) -> String {
+    if text.contains('.') {
+        text
+    } else {
+        text + ".0"
+    }
+}
 pub type IWord = IntType;
 
 #[derive(Clone, Debug, PartialEq)]
@@ -258,7 +442,7 @@ impl AddressOperand {
 
 impl std::fmt::Display for AddressOperand {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
-        let index = self.index.map(|i| format!(":{}", i)).unwrap_or("".into());
+        let index = self.index.map(|i| format!("({})", i)).unwrap_or("".into());
         write!(f, "{}{}", self.address, index)
     }
 }
@@ -275,7 +459,7 @@ impl std::fmt::Display for ConstOperand {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
         match self {
             ConstOperand::SignedIWord(c) => write!(f, "{:+}", c),
-            ConstOperand::SignedFWord(c) => write!(f, "{:+}", c),
+            ConstOperand::SignedFWord(c) => write!(f, "{}", float_text(format!("{:+}", c))),
             ConstOperand::SWord(c) => write!(f, "\"{}\"", c),
         }
     }
@@ -300,6 +484,42 @@ impl std::fmt::Display for SimpleAddressOperand {
 pub enum Address {
     Identifier(Identifier),
     NumericAddress(NumericAddress),
+    Expression(Box<Address>, Operator, Box<Address>),
+}
+
+impl Address {
+    /// The identifiers used by the address, in order of appearance.
+    pub fn identifiers(&self) -> Vec<Identifier> {
+        match self {
+            Address::Identifier(identifier) => vec![identifier.clone()],
+            Address::NumericAddress(_) => Vec::new(),
+            Address::Expression(left, _, right) => {
+                let mut identifiers = left.identifiers();
+                identifiers.extend(right.identifiers());
+                identifiers
+            }
+        }
+    }
+
+    fn precedence(&self) -> Option<u8> {
+        match self {
+            Address::Expression(_, operator, _) => Some(operator.precedence()),
+            _ => None,
+        }
+    }
+
+    /// The address with each identifier renamed.
+    pub fn renamed(&self, rename: &impl Fn(&Identifier) -> Identifier) -> Address {
+        match self {
+            Address::Identifier(identifier) => Address::Identifier(rename(identifier)),
+            Address::NumericAddress(address) => Address::NumericAddress(*address),
+            Address::Expression(left, operator, right) => Address::Expression(
+                Box::new(left.renamed(rename)),
+                *operator,
+                Box::new(right.renamed(rename)),
+            ),
+        }
+    }
 }
 
 impl std::fmt::Display for Address {
@@ -307,10 +527,76 @@ impl std::fmt::Display for Address {
         match self {
             Address::Identifier(i) => write!(f, "{}", i),
             Address::NumericAddress(a) => write!(f, "{}", a),
+            Address::Expression(left, operator, right) => {
+                // Operands are parenthesised where the precedence and left to
+                // right order of the operators would otherwise regroup them.
+                let precedence = operator.precedence();
+                let left = match left.precedence() {
+                    Some(left_precedence) if left_precedence < precedence => format!("({})", left),
+                    _ => left.to_string(),
+                };
+                let right = match right.precedence() {
+                    Some(right_precedence) if right_precedence <= precedence => {
+                        format!("({})", right)
+                    }
+                    _ => right.to_string(),
+                };
+                write!(f, "{}{}{}", left, operator, right)
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+}
+
+impl Operator {
+    fn precedence(&self) -> u8 {
+        match self {
+            Operator::Add | Operator::Subtract => 1,
+            Operator::Multiply | Operator::Divide => 2,
+        }
+    }
+
+    pub fn apply(&self, left: IntType, right: IntType) -> Option<IntType> {
+        match self {
+            Operator::Add => left.checked_add(right),
+            Operator::Subtract => left.checked_sub(right),
+            Operator::Multiply => left.checked_mul(right),
+            Operator::Divide => left.checked_div(right),
         }
     }
 }
 
+impl From<char> for Operator {
+    fn from(c: char) -> Self {
+        match c {
+            '+' => Operator::Add,
+            '-' => Operator::Subtract,
+            '*' => Operator::Multiply,
+            '/' => Operator::Divide,
+            _ => unreachable!(),
+        }
+    }
+}
+
+impl std::fmt::Display for Operator {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::result::Result<(), std::fmt::Error> {
+        let operator = match self {
+            Operator::Add => '+',
+            Operator::Subtract => '-',
+            Operator::Multiply => '*',
+            Operator::Divide => '/',
+        };
+        write!(f, "{}", operator)
+    }
+}
+
 pub type NumericAddress = usize;
 
 pub type Character = char;
@@ -410,6 +696,38 @@ pub enum Mnemonic {
     PAGE,
     RND,
     ABS,
+    READER,
+    PUNCH,
+}
+
+impl Mnemonic {
+    /// Whether the mnemonic names a library routine, which is called by EXTRA and
+    /// takes its accumulator in the address part of the instruction.
+    pub fn is_library_routine(&self) -> bool {
+        matches!(
+            self,
+            Mnemonic::SQRT
+                | Mnemonic::LN
+                | Mnemonic::EXP
+                | Mnemonic::READ
+                | Mnemonic::PRINT
+                | Mnemonic::SIN
+                | Mnemonic::COS
+                | Mnemonic::TAN
+                | Mnemonic::ATN
+                | Mnemonic::STOP
+                | Mnemonic::LINE
+                | Mnemonic::INT
+                | Mnemonic::FRAC
+                | Mnemonic::FLOAT
+                | Mnemonic::CAPN
+                | Mnemonic::PAGE
+                | Mnemonic::RND
+                | Mnemonic::ABS
+                | Mnemonic::READER
+                | Mnemonic::PUNCH
+        )
+    }
 }
 
 impl std::fmt::Display for Mnemonic {
@@ -0,0 +1,185 @@
+use super::assembler::{position, resolve_locations};
+use super::assembly::{Allocation, Assembly, Location};
+use super::ast::{
+    Identifier, Position, SimpleAddressOperand, SourceLine, SourceWord, StoreOperand,
+};
+
+use std::collections::{BTreeMap, HashMap};
+
+// ****************************************************************************
+// The end of a listing cross-references the program. The symbol table gives
+// each identifier, in name order, with its value and type, the line that
+// defines it, and every line that uses it as an operand:
+//
+//     code    names a P-word
+//     data    names any other word placed or reserved by the program
+//     auto    is given a word by the assembler, as it is not defined
+//     equ     is given a value by EQU
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SymbolType {
+    Code,
+    Data,
+    Auto,
+    Equ,
+}
+
+impl std::fmt::Display for SymbolType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SymbolType::Code => f.pad("code"),
+            SymbolType::Data => f.pad("data"),
+            SymbolType::Auto => f.pad("auto"),
+            SymbolType::Equ => f.pad("equ"),
+        }
+    }
+}
+
+/// The symbol table of an assembly whose storage has been allocated, which is
+/// empty when there are no symbols.
+pub fn symbol_table(ast: &[SourceLine], assembly: &Assembly) -> Vec<String> {
+    let ast = resolve_locations(ast);
+    let definitions = ast
+        .iter()
+        .enumerate()
+        .filter_map(|(i, line)| line.label().name().map(|name| (name, position(i, line))))
+        .collect::<HashMap<_, _>>();
+    let uses = identifier_uses(&ast);
+
+    let symbols = assembly
+        .symbols()
+        .iter()
+        .collect::<BTreeMap<&Identifier, &Location>>();
+    if symbols.is_empty() {
+        return Vec::new();
+    }
+    let width = symbols
+        .keys()
+        .map(|name| name.len())
+        .max()
+        .unwrap_or(0)
+        .max(8)
+        + 2;
+
+    let mut lines = vec![format!(
+        "{:<width$}{:<6}{:<6}{:<9}USED ON LINES",
+        "NAME", "VALUE", "TYPE", "DEFINED"
+    )];
+    for (name, location) in symbols {
+        let defined = definitions
+            .get(name)
+            .map_or(String::new(), Position::to_string);
+        let used = uses
+            .get(name)
+            .map_or(String::new(), |lines| join_lines(lines));
+        let line = format!(
+            "{:<width$}{:04}  {:<6}{:<9}{}",
+            name,
+            location,
+            symbol_type(name, *location, assembly),
+            defined,
+            used
+        );
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+fn symbol_type(name: &str, location: Location, assembly: &Assembly) -> SymbolType {
+    let is_allocated = matches!(
+        assembly.storage().get(&location),
+        Some(Allocation::Identifier(identifier)) if identifier == name
+    );
+    if is_allocated {
+        SymbolType::Auto
+    } else if assembly.is_constant(name) {
+        SymbolType::Equ
+    } else if matches!(assembly.content(location), Some(SourceWord::PWord(_))) {
+        SymbolType::Code
+    } else {
+        SymbolType::Data
+    }
+}
+
+// The lines on which each identifier is used by an operand, once per line.
+fn identifier_uses(ast: &[SourceLine]) -> HashMap<Identifier, Vec<Position>> {
+    let mut uses = HashMap::<Identifier, Vec<Position>>::new();
+    for (i, line) in ast.iter().enumerate() {
+        let Some(SourceWord::PWord(pword)) = line.source_program_word() else {
+            continue;
+        };
+        let StoreOperand::AddressOperand(operand) = pword.store_operand() else {
+            continue;
+        };
+        let address = match operand.address() {
+            SimpleAddressOperand::DirectAddress(address) => address,
+            SimpleAddressOperand::IndirectAddress(address) => address,
+        };
+        let position = position(i, line);
+        for identifier in address.identifiers() {
+            let lines = uses.entry(identifier).or_default();
+            if lines.last() != Some(&position) {
+                lines.push(position.clone());
+            }
+        }
+    }
+    uses
+}
+
+fn join_lines(lines: &[Position]) -> String {
+    lines
+        .iter()
+        .map(|line| line.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bbcx::assembler::Assembler;
+    use crate::bbcx::parser::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn cross_reference(input: &str) -> Vec<String> {
+        let ast = input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                Parser::parse_line(line)
+                    .unwrap()
+                    .with_position(Position::new(i + 1))
+            })
+            .collect::<Vec<_>>();
+        let assembly = Assembler::default()
+            .build(&ast)
+            .and_then(|assembly| assembly.allocate_storage(1024))
+            .unwrap();
+        symbol_table(&ast, &assembly)
+    }
+
+    #[test]
+    fn will_cross_reference_symbols() {
+        let symbols = cross_reference(
+            r#"SIZE:   EQU     4
+START:  TAKE    1, +1
+LOOP:   ADD     1, COUNT
+        SUBT    1, +1
+        JNZ     1, LOOP
+        PUT     1, TABLE+SIZE
+        STOP
+TABLE:  RESERVE 4"#,
+        );
+        assert_eq!(
+            symbols,
+            vec![
+                "NAME      VALUE TYPE  DEFINED  USED ON LINES",
+                "COUNT     1023  auto           3",
+                "LOOP      0009  code  3        5",
+                "SIZE      0004  equ   1        6",
+                "START     0008  code  2",
+                "TABLE     0014  data  8        6",
+            ]
+        );
+    }
+}
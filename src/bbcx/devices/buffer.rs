@@ -0,0 +1,58 @@
+use super::Device;
+
+use std::collections::VecDeque;
+use std::io::Result;
+
+/// An in-memory device. Input is supplied up front and output is retained so
+/// that it can be inspected once the program has run.
+#[derive(Debug, Default)]
+pub struct Buffer {
+    input: VecDeque<u8>,
+    output: Vec<u8>,
+}
+
+impl Buffer {
+    pub fn new(input: &str) -> Self {
+        Self {
+            input: input.bytes().collect(),
+            output: Vec::new(),
+        }
+    }
+
+    pub fn output(&self) -> &[u8] {
+        &self.output
+    }
+}
+
+impl Device for Buffer {
+    fn read_char(&mut self) -> Result<Option<u8>> {
+        Ok(self.input.pop_front())
+    }
+
+    fn write_chars(&mut self, chars: &[u8]) -> Result<()> {
+        self.output.extend_from_slice(chars);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn will_read_input_until_exhausted() {
+        let mut buffer = Buffer::new("A");
+        assert_eq!(buffer.read_char().unwrap(), Some(b'A'));
+        assert_eq!(buffer.read_char().unwrap(), None);
+        assert_eq!(buffer.read_char().unwrap(), None);
+    }
+
+    #[test]
+    fn will_retain_output() {
+        let mut buffer = Buffer::default();
+        buffer.write_chars(b"AB").unwrap();
+        buffer.write_chars(b"C").unwrap();
+        assert_eq!(buffer.output(), b"ABC");
+    }
+}
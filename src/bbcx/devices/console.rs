@@ -0,0 +1,35 @@
+use super::Device;
+
+use std::io::{self, Read, Result, Write};
+
+/// The terminal running the program. Input is read from stdin, and output is
+/// written to stdout, which is line buffered.
+#[derive(Debug, Default)]
+pub struct Console {
+    eof: bool,
+}
+
+impl Device for Console {
+    fn read_char(&mut self) -> Result<Option<u8>> {
+        if self.eof {
+            return Ok(None);
+        }
+
+        let mut buffer = [0u8; 1];
+        match io::stdin().read(&mut buffer)? {
+            0 => {
+                self.eof = true;
+                Ok(None)
+            }
+            _ => Ok(Some(buffer[0])),
+        }
+    }
+
+    fn write_chars(&mut self, chars: &[u8]) -> Result<()> {
+        io::stdout().write_all(chars)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        io::stdout().flush()
+    }
+}
@@ -0,0 +1,208 @@
+use super::Device;
+
+use crate::pager::Pager;
+
+use std::io::{Result, Write};
+
+// ****************************************************************************
+// The line printer emulates the printers on which the historical programs were
+// listed. Output is printed on pages of a fixed length and width; a line that is
+// too wide is folded onto the next line and a full page is thrown with a form
+// feed. A page is only thrown when something is printed on the next, so output
+// never ends with a form feed. Each page can start with a header line holding a
+// title and the page number. The PAGE library routine throws the current page.
+
+pub struct LinePrinter<W: Write> {
+    inner: W,
+    page_width: usize,
+    title: Option<String>,
+    numbered: bool,
+    pager: Pager,
+    column: usize,
+}
+
+impl<W: Write> LinePrinter<W> {
+    const FORM_FEED: u8 = Pager::FORM_FEED as u8;
+    const HEADER_LINES: usize = 2;
+
+    pub fn new(inner: W, page_length: usize, page_width: usize) -> Self {
+        Self {
+            inner,
+            page_width: page_width.max(1),
+            title: None,
+            numbered: false,
+            pager: Pager::new(page_length.max(LinePrinter::<W>::HEADER_LINES + 1)),
+            column: 0,
+        }
+    }
+
+    pub fn with_title(mut self, title: Option<String>) -> Self {
+        self.title = title;
+        self
+    }
+
+    pub fn with_page_numbers(mut self, numbered: bool) -> Self {
+        self.numbered = numbered;
+        self
+    }
+
+    fn print(&mut self, byte: u8) -> Result<()> {
+        match byte {
+            b'\r' => Ok(()),
+            b'\n' => {
+                self.start_page()?;
+                self.line_feed()
+            }
+            LinePrinter::<W>::FORM_FEED => {
+                if !self.pager.is_top_of_page() {
+                    if self.column != 0 {
+                        self.inner.write_all(b"\n")?;
+                    }
+                    self.column = 0;
+                    self.pager.throw_page();
+                }
+                Ok(())
+            }
+            byte => {
+                self.start_page()?;
+                if self.column == self.page_width {
+                    self.line_feed()?;
+                    self.start_page()?;
+                }
+                self.inner.write_all(&[byte])?;
+                self.column += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn start_page(&mut self) -> Result<()> {
+        if self.pager.is_top_of_page() {
+            let header_lines = if self.has_header() {
+                LinePrinter::<W>::HEADER_LINES
+            } else {
+                0
+            };
+            if self.pager.start_page(header_lines) {
+                self.inner.write_all(&[LinePrinter::<W>::FORM_FEED])?;
+            }
+            if let Some(header) = self.header() {
+                self.inner.write_all(header.as_bytes())?;
+                self.inner.write_all(b"\n\n")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn has_header(&self) -> bool {
+        self.title.is_some() || self.numbered
+    }
+
+    fn header(&self) -> Option<String> {
+        let number = if self.numbered {
+            format!("PAGE {:>4}", self.pager.page())
+        } else {
+            String::new()
+        };
+        self.has_header().then(|| {
+            let title = self.title.clone().unwrap_or_default();
+            let width = self.page_width.saturating_sub(number.len());
+            let header = format!("{:<width$}{}", title, number, width = width);
+            header.chars().take(self.page_width).collect::<String>()
+        })
+    }
+
+    fn line_feed(&mut self) -> Result<()> {
+        self.inner.write_all(b"\n")?;
+        self.column = 0;
+        self.pager.end_line();
+        Ok(())
+    }
+
+    #[cfg(test)]
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for LinePrinter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for byte in buf {
+            self.print(*byte)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Device for LinePrinter<W> {
+    fn write_chars(&mut self, chars: &[u8]) -> Result<()> {
+        self.write_all(chars)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Write::flush(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn print(printer: LinePrinter<Vec<u8>>, text: &str) -> String {
+        let mut printer = printer;
+        printer.write_all(text.as_bytes()).unwrap();
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn will_print_lines() {
+        let printer = LinePrinter::new(Vec::new(), 66, 132);
+        assert_eq!(print(printer, "AB\nCD\n"), "AB\nCD\n");
+    }
+
+    #[test]
+    fn will_fold_lines_wider_than_page() {
+        let printer = LinePrinter::new(Vec::new(), 66, 4);
+        assert_eq!(print(printer, "ABCDEFGHIJ\n"), "ABCD\nEFGH\nIJ\n");
+    }
+
+    #[test]
+    fn will_throw_page_when_full() {
+        let printer = LinePrinter::new(Vec::new(), 3, 132);
+        assert_eq!(print(printer, "A\nB\nC\nD\n"), "A\nB\nC\n\x0cD\n");
+    }
+
+    #[test]
+    fn will_not_end_with_form_feed_when_page_is_full() {
+        let printer = LinePrinter::new(Vec::new(), 3, 132);
+        assert_eq!(print(printer, "A\nB\nC\n"), "A\nB\nC\n");
+    }
+
+    #[test]
+    fn will_throw_page_on_form_feed() {
+        let printer = LinePrinter::new(Vec::new(), 66, 132);
+        assert_eq!(print(printer, "AB\x0cCD\n"), "AB\n\x0cCD\n");
+    }
+
+    #[test]
+    fn will_not_throw_blank_page() {
+        let printer = LinePrinter::new(Vec::new(), 66, 132);
+        assert_eq!(print(printer, "\x0c\x0cAB\n"), "AB\n");
+    }
+
+    #[test]
+    fn will_print_page_headers() {
+        let printer = LinePrinter::new(Vec::new(), 4, 20)
+            .with_title(Some("SQRT".into()))
+            .with_page_numbers(true);
+        assert_eq!(
+            print(printer, "A\nB\nC\n"),
+            "SQRT       PAGE    1\n\nA\nB\n\x0cSQRT       PAGE    2\n\nC\n"
+        );
+    }
+}
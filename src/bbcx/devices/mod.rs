@@ -0,0 +1,143 @@
+#[cfg(test)]
+mod buffer;
+mod console;
+mod line_printer;
+mod paper_tape;
+mod teletype;
+
+#[cfg(test)]
+pub use self::buffer::Buffer;
+pub use self::console::Console;
+pub use self::line_printer::LinePrinter;
+pub use self::paper_tape::{Frame as TapeFrame, PaperTapePunch, PaperTapeReader};
+pub use self::teletype::Teletype;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::rc::Rc;
+
+/// A peripheral attached to the executor. A device decides how it buffers its
+/// characters and when its input is exhausted. Once `read_char` has returned
+/// `None` it continues to do so.
+pub trait Device {
+    fn read_char(&mut self) -> Result<Option<u8>> {
+        Err(Error::new(ErrorKind::Unsupported, "device cannot be read"))
+    }
+
+    fn write_chars(&mut self, _chars: &[u8]) -> Result<()> {
+        Err(Error::new(
+            ErrorKind::Unsupported,
+            "device cannot be written",
+        ))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The channels of the executor. A program reads from `Input` and writes to
+/// `Output` until it selects the paper tape `Reader` or `Punch` with the
+/// READER and PUNCH library routines.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Channel {
+    Input,
+    Output,
+    Reader,
+    Punch,
+}
+
+/// The devices attached to each of the executor's channels. The same device
+/// may be attached to more than one channel.
+#[derive(Clone, Default)]
+pub struct DeviceTable {
+    devices: HashMap<Channel, Rc<RefCell<dyn Device>>>,
+}
+
+impl DeviceTable {
+    pub fn console() -> Self {
+        let console = Rc::new(RefCell::new(Console::default()));
+        Self::default()
+            .with_device(Channel::Input, console.clone())
+            .with_device(Channel::Output, console)
+    }
+
+    pub fn with_device(mut self, channel: Channel, device: Rc<RefCell<dyn Device>>) -> Self {
+        self.devices.insert(channel, device);
+        self
+    }
+
+    fn device(&self, channel: Channel) -> Result<&Rc<RefCell<dyn Device>>> {
+        self.devices.get(&channel).ok_or(Error::new(
+            ErrorKind::NotConnected,
+            format!("no device attached to {:?} channel", channel),
+        ))
+    }
+
+    pub fn attached(&self, channel: Channel) -> Result<()> {
+        self.device(channel).map(|_| ())
+    }
+
+    pub fn read_char(&self, channel: Channel) -> Result<Option<u8>> {
+        self.device(channel)?.borrow_mut().read_char()
+    }
+
+    pub fn write_chars(&self, channel: Channel, chars: &[u8]) -> Result<()> {
+        self.device(channel)?.borrow_mut().write_chars(chars)
+    }
+
+    pub fn flush(&self, channel: Channel) -> Result<()> {
+        self.device(channel)?.borrow_mut().flush()
+    }
+
+    pub fn flush_all(&self) -> Result<()> {
+        self.devices
+            .values()
+            .try_for_each(|device| device.borrow_mut().flush())
+    }
+}
+
+impl std::fmt::Debug for DeviceTable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.devices.keys()).finish()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn will_route_channels_to_attached_devices() {
+        let input = Rc::new(RefCell::new(Buffer::new("AB")));
+        let output = Rc::new(RefCell::new(Buffer::default()));
+        let devices = DeviceTable::default()
+            .with_device(Channel::Input, input.clone())
+            .with_device(Channel::Output, output.clone());
+
+        let char = devices.read_char(Channel::Input).unwrap();
+        devices
+            .write_chars(Channel::Output, &[char.unwrap()])
+            .unwrap();
+
+        assert_eq!(input.borrow().output(), b"");
+        assert_eq!(output.borrow().output(), b"A");
+    }
+
+    #[test]
+    fn will_fail_on_unattached_channel() {
+        let devices = DeviceTable::default();
+        let result = devices.read_char(Channel::Input).map_err(|e| e.kind());
+        assert_eq!(result, Err(ErrorKind::NotConnected));
+    }
+
+    #[test]
+    fn will_fail_to_read_output_only_device() {
+        let teletype = Rc::new(RefCell::new(Teletype::new(Vec::new(), 72, false)));
+        let devices = DeviceTable::default().with_device(Channel::Input, teletype);
+        let result = devices.read_char(Channel::Input).map_err(|e| e.kind());
+        assert_eq!(result, Err(ErrorKind::Unsupported));
+    }
+}
@@ -0,0 +1,285 @@
+use super::Device;
+
+use crate::bbcx::charset::CharSet;
+
+use std::io::{Read, Result, Write};
+
+// ****************************************************************************
+// Paper tape images hold one frame per byte. Six hole frames carry the BBC-X
+// character set directly. Five hole frames use the Elliott telecode, where the
+// letter and figure shift frames select between two sets of characters. Blank
+// frames are runout and are ignored when the tape is read.
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Frame {
+    Five,
+    #[default]
+    Six,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Shift {
+    Letters,
+    Figures,
+}
+
+struct Telecode;
+
+impl Telecode {
+    const BLANK: u8 = 0;
+    const FIGURE_SHIFT: u8 = 27;
+    const SPACE: u8 = 28;
+    const CARRIAGE_RETURN: u8 = 29;
+    const LINE_FEED: u8 = 30;
+    const LETTER_SHIFT: u8 = 31;
+
+    // Indexed by frame value; `0` marks a frame without a BBC-X character.
+    #[rustfmt::skip]
+    const FIGURES: [u8; 27] = [
+        0, b'1', b'2', b'*', b'4', 0, b'=', b'7', b'8', b'\'', b',', b'+', b':', b'-',
+        b'.', 0, b'0', b'(', b')', b'3', b'?', b'5', b'6', b'/', b'@', b'9', 0,
+    ];
+
+    fn decode(frame: u8, shift: Shift) -> Option<u8> {
+        match (frame, shift) {
+            (1..=26, Shift::Letters) => Some(b'A' + frame - 1),
+            (1..=26, Shift::Figures) => Some(Telecode::FIGURES[frame as usize]).filter(|c| *c != 0),
+            _ => None,
+        }
+    }
+
+    fn encode(char: u8) -> Option<(u8, Shift)> {
+        match char {
+            b'A'..=b'Z' => Some((char - b'A' + 1, Shift::Letters)),
+            _ => Telecode::FIGURES
+                .iter()
+                .position(|c| *c == char && char != 0)
+                .map(|frame| (frame as u8, Shift::Figures)),
+        }
+    }
+}
+
+pub struct PaperTapeReader<R: Read> {
+    inner: R,
+    frame: Frame,
+    shift: Shift,
+}
+
+impl<R: Read> PaperTapeReader<R> {
+    const SUBSTITUTE: u8 = b'?';
+
+    pub fn new(inner: R, frame: Frame) -> Self {
+        Self {
+            inner,
+            frame,
+            shift: Shift::Letters,
+        }
+    }
+
+    fn read_frame(&mut self) -> Result<Option<u8>> {
+        let mut buffer = [0u8; 1];
+        match self.inner.read(&mut buffer)? {
+            0 => Ok(None),
+            _ => Ok(Some(buffer[0])),
+        }
+    }
+
+    fn next_char(&mut self) -> Result<Option<u8>> {
+        while let Some(frame) = self.read_frame()? {
+            let char = match self.frame {
+                Frame::Six => match frame & 0o77 {
+                    0 => None,
+                    bits => CharSet::bits_to_char(bits as u64),
+                },
+                Frame::Five => match frame & 0o37 {
+                    Telecode::BLANK | Telecode::CARRIAGE_RETURN => None,
+                    Telecode::FIGURE_SHIFT => {
+                        self.shift = Shift::Figures;
+                        None
+                    }
+                    Telecode::LETTER_SHIFT => {
+                        self.shift = Shift::Letters;
+                        None
+                    }
+                    Telecode::SPACE => Some(b' '),
+                    Telecode::LINE_FEED => Some(b'\n'),
+                    frame => Some(
+                        Telecode::decode(frame, self.shift)
+                            .unwrap_or(PaperTapeReader::<R>::SUBSTITUTE),
+                    ),
+                },
+            };
+            if char.is_some() {
+                return Ok(char);
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl<R: Read> Read for PaperTapeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let mut count = 0;
+        while count < buf.len() {
+            match self.next_char()? {
+                Some(char) => {
+                    buf[count] = char;
+                    count += 1;
+                }
+                None => break,
+            }
+        }
+        Ok(count)
+    }
+}
+
+pub struct PaperTapePunch<W: Write> {
+    inner: W,
+    frame: Frame,
+    shift: Option<Shift>,
+}
+
+impl<W: Write> PaperTapePunch<W> {
+    const SUBSTITUTE: u8 = b'?';
+
+    pub fn new(inner: W, frame: Frame) -> Self {
+        Self {
+            inner,
+            frame,
+            shift: None,
+        }
+    }
+
+    fn punch(&mut self, char: u8) -> Result<()> {
+        let char = match CharSet::char_to_bits(char) {
+            Some(_) => char,
+            None if char == b'\x0c' => b'\n',
+            None => char.to_ascii_uppercase(),
+        };
+        match self.frame {
+            Frame::Six => {
+                let bits = CharSet::char_to_bits(char)
+                    .or(CharSet::char_to_bits(PaperTapePunch::<W>::SUBSTITUTE))
+                    .unwrap();
+                self.inner.write_all(&[bits as u8])
+            }
+            Frame::Five => match char {
+                b'\0' | b'\r' => Ok(()),
+                b' ' => self.inner.write_all(&[Telecode::SPACE]),
+                b'\n' => self
+                    .inner
+                    .write_all(&[Telecode::CARRIAGE_RETURN, Telecode::LINE_FEED]),
+                char => {
+                    let (frame, shift) = Telecode::encode(char)
+                        .or(Telecode::encode(PaperTapePunch::<W>::SUBSTITUTE))
+                        .unwrap();
+                    if self.shift != Some(shift) {
+                        let shift_frame = match shift {
+                            Shift::Letters => Telecode::LETTER_SHIFT,
+                            Shift::Figures => Telecode::FIGURE_SHIFT,
+                        };
+                        self.inner.write_all(&[shift_frame])?;
+                        self.shift = Some(shift);
+                    }
+                    self.inner.write_all(&[frame])
+                }
+            },
+        }
+    }
+
+    #[cfg(test)]
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for PaperTapePunch<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for char in buf {
+            self.punch(*char)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Device for PaperTapeReader<R> {
+    fn read_char(&mut self) -> Result<Option<u8>> {
+        self.next_char()
+    }
+}
+
+impl<W: Write> Device for PaperTapePunch<W> {
+    fn write_chars(&mut self, chars: &[u8]) -> Result<()> {
+        self.write_all(chars)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Write::flush(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn punch(text: &str, frame: Frame) -> Vec<u8> {
+        let mut punch = PaperTapePunch::new(Vec::new(), frame);
+        punch.write_all(text.as_bytes()).unwrap();
+        punch.into_inner()
+    }
+
+    fn read(tape: &[u8], frame: Frame) -> String {
+        let mut reader = PaperTapeReader::new(tape, frame);
+        let mut text = String::new();
+        reader.read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn will_punch_six_hole_frames() {
+        assert_eq!(punch("AB 12\n", Frame::Six), vec![1, 2, 61, 33, 34, 62]);
+    }
+
+    #[test]
+    fn will_read_six_hole_frames_ignoring_runout() {
+        assert_eq!(
+            read(&[0, 0, 1, 2, 61, 33, 34, 62, 0], Frame::Six),
+            "AB 12\n"
+        );
+    }
+
+    #[test]
+    fn will_punch_five_hole_frames_with_shifts() {
+        assert_eq!(
+            punch("AB 12\n", Frame::Five),
+            vec![31, 1, 2, 28, 27, 1, 2, 29, 30]
+        );
+    }
+
+    #[test]
+    fn will_read_five_hole_frames_with_shifts() {
+        assert_eq!(
+            read(&[0, 31, 1, 2, 28, 27, 1, 2, 29, 30, 31, 3], Frame::Five),
+            "AB 12\nC"
+        );
+    }
+
+    #[test]
+    fn will_round_trip_characters() {
+        let text = "HELLO, WORLD. (3+4)*2=14?\n";
+        for frame in [Frame::Five, Frame::Six] {
+            assert_eq!(read(&punch(text, frame), frame), text);
+        }
+    }
+
+    #[test]
+    fn will_punch_substitute_for_unknown_characters() {
+        assert_eq!(read(&punch("A$B", Frame::Five), Frame::Five), "A?B");
+        assert_eq!(read(&punch("A$B", Frame::Six), Frame::Six), "A?B");
+    }
+}
@@ -0,0 +1,134 @@
+use super::Device;
+
+use crate::bbcx::charset::CharSet;
+
+use std::io::{Result, Write};
+use std::thread;
+use std::time::Duration;
+
+// ****************************************************************************
+// The teletype emulates the terminals on which BBC-X programs were run. It only
+// prints the 6-bit BBC-X character set, in upper case, on a carriage of fixed
+// width. Each new line is a carriage return followed by a line feed and, when
+// throttled, characters are printed at the teletype's 10 characters per second.
+
+pub struct Teletype<W: Write> {
+    inner: W,
+    carriage_width: usize,
+    throttle: bool,
+    column: usize,
+}
+
+impl<W: Write> Teletype<W> {
+    const CHARACTERS_PER_SECOND: u64 = 10;
+    const SUBSTITUTE: u8 = b'?';
+
+    pub fn new(inner: W, carriage_width: usize, throttle: bool) -> Self {
+        Self {
+            inner,
+            carriage_width: carriage_width.max(1),
+            throttle,
+            column: 0,
+        }
+    }
+
+    fn print(&mut self, byte: u8) -> Result<()> {
+        match byte.to_ascii_uppercase() {
+            b'\0' | b'\r' => Ok(()),
+            b'\n' | b'\x0c' => self.new_line(),
+            byte => {
+                if self.column == self.carriage_width {
+                    self.new_line()?;
+                }
+                let byte = CharSet::char_to_bits(byte).map_or(Teletype::<W>::SUBSTITUTE, |_| byte);
+                self.emit(&[byte])?;
+                self.column += 1;
+                Ok(())
+            }
+        }
+    }
+
+    fn new_line(&mut self) -> Result<()> {
+        self.column = 0;
+        self.emit(b"\r\n")
+    }
+
+    fn emit(&mut self, bytes: &[u8]) -> Result<()> {
+        if self.throttle {
+            let delay = Duration::from_millis(1000 / Teletype::<W>::CHARACTERS_PER_SECOND);
+            for byte in bytes {
+                self.inner.write_all(&[*byte])?;
+                self.inner.flush()?;
+                thread::sleep(delay);
+            }
+            Ok(())
+        } else {
+            self.inner.write_all(bytes)
+        }
+    }
+
+    #[cfg(test)]
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for Teletype<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        for byte in buf {
+            self.print(*byte)?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<W: Write> Device for Teletype<W> {
+    fn write_chars(&mut self, chars: &[u8]) -> Result<()> {
+        self.write_all(chars)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Write::flush(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn print(text: &str, carriage_width: usize) -> String {
+        let mut teletype = Teletype::new(Vec::new(), carriage_width, false);
+        teletype.write_all(text.as_bytes()).unwrap();
+        String::from_utf8(teletype.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn will_print_in_upper_case() {
+        assert_eq!(print("Hello, World.", 72), "HELLO, WORLD.");
+    }
+
+    #[test]
+    fn will_print_new_line_as_carriage_return_line_feed() {
+        assert_eq!(print("AB\nCD\n", 72), "AB\r\nCD\r\n");
+    }
+
+    #[test]
+    fn will_substitute_characters_not_in_charset() {
+        assert_eq!(print("A!B\tC$", 72), "A?B?C?");
+    }
+
+    #[test]
+    fn will_wrap_at_carriage_width() {
+        assert_eq!(print("ABCDEFG", 3), "ABC\r\nDEF\r\nG");
+    }
+
+    #[test]
+    fn will_not_wrap_when_line_exactly_fills_carriage() {
+        assert_eq!(print("ABC\nDEF", 3), "ABC\r\nDEF");
+    }
+}
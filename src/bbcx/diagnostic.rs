@@ -0,0 +1,322 @@
+use super::grammar::*;
+
+use pom::utf8::end;
+
+// ****************************************************************************
+// The grammar either accepts a whole line or rejects it. When it is rejected
+// the line is re-parsed one construct at a time, so that the position of the
+// first construct that cannot be recognised can be reported.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Expected {
+    Address,
+    Count,
+    Text,
+    Identifiers,
+    SourceWord,
+    SWord,
+    Mnemonic,
+    Accumulator,
+    StoreOperand,
+    EndOfLine,
+}
+
+impl std::fmt::Display for Expected {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let expected = match self {
+            Expected::Address => "address",
+            Expected::Count => "word count",
+            Expected::Text => "quoted text",
+            Expected::Identifiers => "identifiers separated by commas",
+            Expected::SourceWord => "source word",
+            Expected::SWord => "S-word of 1 to 4 characters",
+            Expected::Mnemonic => "mnemonic",
+            Expected::Accumulator => "accumulator (0 to 7)",
+            Expected::StoreOperand => "store operand",
+            Expected::EndOfLine => "comment or end of line",
+        };
+        write!(f, "{}", expected)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Diagnostic {
+    line: String,
+    column: usize,
+    expected: Expected,
+    suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    const MAX_SUGGESTION_DISTANCE: usize = 2;
+
+    pub fn new(line: &str) -> Self {
+        let text = line.trim();
+        let lead = line.len() - line.trim_start().len();
+        let (expected, position, suggestion) = locate(text.as_bytes());
+        Self {
+            line: line.into(),
+            column: lead + position + 1,
+            expected,
+            suggestion,
+        }
+    }
+
+    #[cfg(test)]
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    #[cfg(test)]
+    pub fn expected(&self) -> Expected {
+        self.expected
+    }
+
+    fn found(&self) -> String {
+        let token = self.line[self.column - 1..]
+            .split_whitespace()
+            .next()
+            .unwrap_or_default();
+        if token.is_empty() {
+            "end of line".into()
+        } else {
+            format!("\"{}\"", token)
+        }
+    }
+
+    fn caret(&self) -> String {
+        let pad = self.line[..self.column - 1]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+        format!("{}^", pad)
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}\n{} expected {} at column {}, found {}",
+            self.line,
+            self.caret(),
+            self.expected,
+            self.column,
+            self.found()
+        )?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, "; did you mean {}?", suggestion)?;
+        }
+        Ok(())
+    }
+}
+
+fn locate(input: &[u8]) -> (Expected, usize, Option<String>) {
+    let position = location()
+        .parse_at(input, 0)
+        .map_or(0, |(_, position)| position);
+    let (_, position) = label().parse_at(input, position).unwrap();
+    let position = skip_ws(input, position);
+
+    let word_end = match input.get(position) {
+        None | Some(b';') => position,
+        Some(_) if keyword(input, position).is_some() => {
+            match directive().parse_at(input, position) {
+                Ok((_, word_end)) => word_end,
+                Err(_) => {
+                    let keyword = keyword(input, position).unwrap();
+                    let expected = match keyword {
+                        "RESERVE" => Expected::Count,
+                        "TEXT" => Expected::Text,
+                        "EXPORT" | "IMPORT" => Expected::Identifiers,
+                        _ => Expected::Address,
+                    };
+                    let operand = skip_ws(input, position + keyword.len());
+                    return (expected, operand, None);
+                }
+            }
+        }
+        Some(c) if c.is_ascii_uppercase() => match locate_pword(input, position) {
+            Ok(word_end) => word_end,
+            Err(diagnosis) => return diagnosis,
+        },
+        Some(c) => match source_program_word().parse_at(input, position) {
+            Ok((_, word_end)) => word_end,
+            Err(_) if *c == b'"' => return (Expected::SWord, position, None),
+            Err(_) => return (Expected::SourceWord, position, None),
+        },
+    };
+
+    let position = skip_ws(input, word_end);
+    (Expected::EndOfLine, position, None)
+}
+
+type Diagnosis = (Expected, usize, Option<String>);
+
+// The directive keyword at the position, if any.
+fn keyword(input: &[u8], position: usize) -> Option<&'static str> {
+    DIRECTIVES
+        .iter()
+        .find(|keyword| input[position..].starts_with(keyword.as_bytes()))
+        .copied()
+}
+
+fn locate_pword(input: &[u8], start: usize) -> Result<usize, Diagnosis> {
+    let token_end = input[start..]
+        .iter()
+        .position(|c| !c.is_ascii_alphanumeric())
+        .map_or(input.len(), |n| start + n);
+
+    let mnemonic_end = match mnemonic().parse_at(input, start) {
+        Ok((_, end)) if !input.get(end).is_some_and(u8::is_ascii_alphabetic) => end,
+        _ => {
+            let token = String::from_utf8_lossy(&input[start..token_end]);
+            return Err((Expected::Mnemonic, start, suggestion(&token)));
+        }
+    };
+
+    let position = skip_ws(input, mnemonic_end);
+    if let [digit, b',', ..] = input[position..] {
+        if digit.is_ascii_digit() && !(b'0'..=b'7').contains(&digit) {
+            return Err((Expected::Accumulator, position, None));
+        }
+    }
+
+    let (_, position) = acc().parse_at(input, position).unwrap();
+    let position = skip_ws(input, position);
+    let (_, operand_end) = store_operand().parse_at(input, position).unwrap();
+    let rest = skip_ws(input, operand_end);
+    if (comment() - end()).parse_at(input, rest).is_err() && operand_end == position {
+        return Err((Expected::StoreOperand, position, None));
+    }
+
+    Ok(operand_end)
+}
+
+fn skip_ws(input: &[u8], position: usize) -> usize {
+    inline_ws()
+        .parse_at(input, position)
+        .map_or(position, |(_, end)| end)
+}
+
+fn suggestion(token: &str) -> Option<String> {
+    let distances = MNEMONICS
+        .iter()
+        .map(|(tag, _)| (distance(token, tag), *tag))
+        .filter(|(d, _)| *d <= Diagnostic::MAX_SUGGESTION_DISTANCE)
+        .collect::<Vec<_>>();
+    let nearest = distances.iter().map(|(d, _)| *d).min()?;
+    let suggestions = distances
+        .iter()
+        .filter(|(d, _)| *d == nearest)
+        .map(|(_, tag)| *tag)
+        .collect::<Vec<_>>();
+    Some(suggestions.join(" or "))
+}
+
+// Levenshtein distance between two strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn will_report_mnemonic_without_location() {
+        let diagnostic = Diagnostic::new("TAKR 1, 20");
+        assert_eq!(diagnostic.expected(), Expected::Mnemonic);
+        assert_eq!(diagnostic.column(), 1);
+    }
+
+    #[test]
+    fn will_report_invalid_origin() {
+        let diagnostic = Diagnostic::new("ORIGIN START");
+        assert_eq!(diagnostic.expected(), Expected::Address);
+        assert_eq!(diagnostic.column(), 8);
+    }
+
+    #[test]
+    fn will_suggest_misspelt_mnemonic() {
+        let diagnostic = Diagnostic::new("0100    TAKR 1, 20");
+        assert_eq!(
+            diagnostic.to_string(),
+            "0100    TAKR 1, 20\n        ^ expected mnemonic at column 9, found \"TAKR\"; did you mean TAKE?"
+        );
+    }
+
+    #[test]
+    fn will_report_invalid_directive_operands() {
+        let diagnostic = Diagnostic::new("BUF: RESERVE TEN");
+        assert_eq!(diagnostic.expected(), Expected::Count);
+        assert_eq!(diagnostic.column(), 14);
+
+        let diagnostic = Diagnostic::new("TEXT HELLO");
+        assert_eq!(diagnostic.expected(), Expected::Text);
+        assert_eq!(diagnostic.column(), 6);
+
+        let diagnostic = Diagnostic::new("IMPORT 10");
+        assert_eq!(diagnostic.expected(), Expected::Identifiers);
+        assert_eq!(diagnostic.column(), 8);
+    }
+
+    #[test]
+    fn will_report_mnemonic_with_trailing_letters() {
+        let diagnostic = Diagnostic::new("0100 TAKEN 1, 20");
+        assert_eq!(diagnostic.expected(), Expected::Mnemonic);
+        assert_eq!(diagnostic.column(), 6);
+    }
+
+    #[test]
+    fn will_report_invalid_accumulator() {
+        let diagnostic = Diagnostic::new("0100 TAKE 8, 20");
+        assert_eq!(diagnostic.expected(), Expected::Accumulator);
+        assert_eq!(diagnostic.column(), 11);
+    }
+
+    #[test]
+    fn will_report_invalid_store_operand() {
+        let diagnostic = Diagnostic::new("0100 TAKE 1, #20");
+        assert_eq!(diagnostic.expected(), Expected::StoreOperand);
+        assert_eq!(diagnostic.column(), 14);
+    }
+
+    #[test]
+    fn will_report_unterminated_sword() {
+        let diagnostic = Diagnostic::new("0100 \"ABCDE\"");
+        assert_eq!(diagnostic.expected(), Expected::SWord);
+        assert_eq!(diagnostic.column(), 6);
+    }
+
+    #[test]
+    fn will_report_trailing_text() {
+        let diagnostic = Diagnostic::new("  0100 TAKE 1, 20 X");
+        assert_eq!(diagnostic.expected(), Expected::EndOfLine);
+        assert_eq!(diagnostic.column(), 19);
+    }
+
+    #[test]
+    fn will_align_caret_with_tabs() {
+        let diagnostic = Diagnostic::new("0100\tTAKR");
+        assert!(diagnostic.to_string().starts_with("0100\tTAKR\n    \t^"));
+    }
+
+    #[test]
+    fn will_measure_edit_distance() {
+        assert_eq!(distance("TAKR", "TAKE"), 1);
+        assert_eq!(distance("TKAE", "TAKE"), 2);
+        assert_eq!(distance("", "ADD"), 3);
+    }
+}
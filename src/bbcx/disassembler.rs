@@ -0,0 +1,353 @@
+use super::assembler::{Assembler, DEFAULT_ORIGIN};
+use super::assembly::Location;
+use super::ast::{Identifier, Mnemonic, SourceWord};
+use super::grammar::{DIRECTIVES, MNEMONICS};
+use super::memory::{word_to_instruction, Instruction, MemoryConfig, MemoryIndex, Word, WordType};
+use super::object::ObjectImage;
+use super::parser::Parser;
+
+use crate::result::{Error, Result};
+
+use std::collections::{HashMap, HashSet};
+
+// ****************************************************************************
+// A memory image is turned back into source, one line for each word, so that
+// it assembles back to the same image. Locations that are used as operands or
+// jump targets are given labels, taken from the symbols of an object file when
+// there are any, or else made from the location, such as L0012. Library
+// routines are written with their own mnemonics. Shift counts and EXTRA codes
+// are not addresses, so are left as numbers.
+
+/// The source lines for the words of an image.
+pub fn disassemble(image: &ObjectImage) -> Result<Vec<String>> {
+    let labels = labels(image);
+    let mut lines = vec![format!("; Entry {:04}", image.entry())];
+    let mut errors = Vec::new();
+    let mut counter = DEFAULT_ORIGIN;
+    for (location, word) in image.words() {
+        if is_initial_word(*location, word) {
+            continue;
+        }
+        if *location != counter {
+            lines.push(format!("{:<8}{:<8}{}", "", "ORIGIN", location));
+        }
+        let label = labels
+            .get(location)
+            .map_or(String::new(), |label| format!("{}:", label));
+        match source(word, &|instruction| operand(instruction, &labels)) {
+            Some(source) => lines.push(format!("{:<8}{}", label, source)),
+            None => errors.push(word_error(*location, word, "cannot be written as source")),
+        }
+        counter = location + 1;
+    }
+
+    if errors.is_empty() {
+        errors = round_trip_errors(image, &lines);
+    }
+    if errors.is_empty() {
+        Ok(lines)
+    } else {
+        Err(Error::FailedToDisassemble(errors))
+    }
+}
+
+// Location zero is set to zero whenever a program is loaded.
+pub(super) fn is_initial_word(location: Location, word: &Word) -> bool {
+    location == 0 && word.content_type() == WordType::IWord && word.raw_bits() == 0
+}
+
+pub(super) fn word_error(location: Location, word: &Word, error: &str) -> String {
+    format!(
+        "Location {:04}: {:?} {:#010o} {}",
+        location,
+        word.content_type(),
+        word.raw_bits(),
+        error
+    )
+}
+
+fn labels(image: &ObjectImage) -> HashMap<Location, Identifier> {
+    let defined = image
+        .words()
+        .iter()
+        .map(|(location, _)| *location)
+        .filter(|location| *location >= DEFAULT_ORIGIN)
+        .collect::<HashSet<_>>();
+
+    let mut labels = HashMap::new();
+    for (location, identifier) in image.symbols() {
+        if defined.contains(location) && is_label(identifier) {
+            labels.entry(*location).or_insert(identifier.clone());
+        }
+    }
+
+    let mut names = labels.values().cloned().collect::<HashSet<_>>();
+    for (_, word) in image.words() {
+        let target = operand_target(word).filter(|target| defined.contains(target));
+        if let Some(target) = target.filter(|target| !labels.contains_key(target)) {
+            let mut label = format!("L{:04}", target);
+            while names.contains(&label) {
+                label.insert(0, 'L');
+            }
+            names.insert(label.clone());
+            labels.insert(target, label);
+        }
+    }
+    labels
+}
+
+// A symbol can only be used as a label when the grammar reads it as one.
+pub(super) fn is_label(identifier: &str) -> bool {
+    let mut chars = identifier.chars();
+    chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+        && !MNEMONICS
+            .iter()
+            .any(|(mnemonic, _)| *mnemonic == identifier)
+        && !DIRECTIVES.contains(&identifier)
+}
+
+pub(super) fn instruction(word: &Word) -> Option<Instruction> {
+    (word.is_instruction() && word.pword_page_bits() == 0)
+        .then(|| word_to_instruction(word).ok())
+        .flatten()
+}
+
+pub(super) fn has_address_operand(instruction: &Instruction) -> bool {
+    !matches!(
+        instruction.function(),
+        Mnemonic::SHL | Mnemonic::ROT | Mnemonic::DSHL | Mnemonic::DROT | Mnemonic::EXTRA
+    )
+}
+
+fn operand_target(word: &Word) -> Option<Location> {
+    instruction(word)
+        .filter(has_address_operand)
+        .map(|instruction| instruction.address().memory_index())
+}
+
+pub(super) fn library_routine(instruction: &Instruction) -> Option<Mnemonic> {
+    let code = instruction.address().memory_index() as u32;
+    let is_plain = !instruction.index_register().is_indexable() && !instruction.is_indirect();
+    (instruction.function() == Mnemonic::EXTRA && code > 0 && is_plain)
+        .then(|| Mnemonic::try_from(Mnemonic::EXTRA as u32 + code).ok())
+        .flatten()
+}
+
+/// A word in source syntax, with the operand of an instruction written by the
+/// given function.
+pub(super) fn source(word: &Word, operand: &dyn Fn(&Instruction) -> String) -> Option<String> {
+    match word.content_type() {
+        WordType::IWord => word.as_i64().ok().map(|i| format!("{:+}", i)),
+        WordType::FWord => word.as_f64().ok().map(|f| {
+            let text = format!("{:+}", f);
+            if text.contains('.') {
+                text
+            } else {
+                text + ".0"
+            }
+        }),
+        WordType::SWord => word
+            .as_string()
+            .ok()
+            .map(|s| format!("\"{}\"", s))
+            .filter(|text| is_sword_text(text, word)),
+        WordType::PWord => {
+            instruction(word).map(|instruction| pword(&instruction, &operand(&instruction)))
+        }
+        WordType::Undefined => None,
+    }
+}
+
+// Text can only be written when it reads back as the same S-word, which it does
+// not when the word holds a null character after the first, or characters that
+// have no form in source.
+fn is_sword_text(text: &str, word: &Word) -> bool {
+    matches!(
+        Parser::parse_line(text).map(|line| line.source_program_word().cloned()),
+        Ok(Some(SourceWord::SWord(sword))) if Word::try_from(sword.as_str()).ok() == Some(*word)
+    )
+}
+
+fn pword(instruction: &Instruction, operand: &str) -> String {
+    let accumulator = instruction.accumulator().memory_index();
+    match library_routine(instruction) {
+        Some(routine) => format!("{:<8}{}", format!("{:?}", routine), accumulator),
+        None => format!(
+            "{:<8}{}, {}",
+            format!("{:?}", instruction.function()),
+            accumulator,
+            operand
+        ),
+    }
+}
+
+pub(super) fn operand(instruction: &Instruction, labels: &HashMap<Location, Identifier>) -> String {
+    let address = instruction.address().memory_index();
+    let label = labels
+        .get(&address)
+        .filter(|_| has_address_operand(instruction));
+    format!(
+        "{}{}{}",
+        instruction.indirect(),
+        label.map_or(address.to_string(), Identifier::clone),
+        instruction.index_register()
+    )
+}
+
+// The source is assembled again, and each word compared with the original.
+fn round_trip_errors(image: &ObjectImage, lines: &[String]) -> Vec<String> {
+    let ast = match lines
+        .iter()
+        .map(|line| Parser::parse_line(line))
+        .collect::<Result<Vec<_>>>()
+    {
+        Ok(ast) => ast,
+        Err(error) => return vec![error.to_string()],
+    };
+    let memory_size = MemoryConfig::default().size();
+    let rebuilt = match Assembler::default()
+        .build(&ast)
+        .and_then(|assembly| ObjectImage::new(assembly, memory_size))
+    {
+        Ok(rebuilt) => rebuilt,
+        Err(error) => return vec![error.to_string()],
+    };
+
+    let rebuilt = rebuilt.words().iter().copied().collect::<HashMap<_, _>>();
+    image
+        .words()
+        .iter()
+        .filter(|(location, word)| rebuilt.get(location) != Some(word))
+        .map(|(location, word)| word_error(*location, word, "does not assemble back to itself"))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn image(input: &str) -> ObjectImage {
+        let ast = input
+            .lines()
+            .map(|line| Parser::parse_line(line).unwrap())
+            .collect::<Vec<_>>();
+        let assembly = Assembler::default().build(&ast).unwrap();
+        ObjectImage::new(assembly, 1024).unwrap()
+    }
+
+    #[test]
+    fn will_disassemble_to_source_that_assembles_to_the_same_image() {
+        let image = image(
+            r#"START:  TAKE    1, COUNT
+LOOP:   ADD     1, +3
+        PUT     1, *POINTER(2)
+        SKAE    1, 100
+        JUMP    0, LOOP
+        SHL     1, 4
+        PRINT   1
+        EXTRA   2, 0
+        STOP
+COUNT:  +21
+POINTER:-7
+        +1.5
+        "AB:?""#,
+        );
+        let lines = disassemble(&image).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "; Entry 0008",
+                "START:  TAKE    1, COUNT",
+                "LOOP:   ADD     1, L1023",
+                "        PUT     1, *POINTER(2)",
+                "        SKAE    1, 100",
+                "        JUMP    0, LOOP",
+                "        SHL     1, 4",
+                "        PRINT   1",
+                "        EXTRA   2, 0",
+                "        STOP    0",
+                "COUNT:  +21",
+                "POINTER:-7",
+                "        +1.5",
+                "        \"AB:?\"",
+                "        ORIGIN  1023",
+                "L1023:  +3",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_disassemble_memory_dump_with_gaps() {
+        let dump = r#"Memory
+00:00:00.000  -      000000  IWord 0o00000000 0
+00:00:00.000  -      000001  IWord 0o00000052 42
+00:00:00.000  -      000100  PWord 0o10100001 TAKE 1, 1
+00:00:00.000  -      000101  PWord 0o60000144 JUMP 0, 100
+00:00:00.000  -      000102  FWord 0o20000000 2.0"#;
+        let image = ObjectImage::from_memory_dump(dump).unwrap();
+        let lines = disassemble(&image).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "; Entry 0100",
+                "        ORIGIN  100",
+                "L0100:  TAKE    1, 1",
+                "        JUMP    0, L0100",
+                "        +2.0",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_disassemble_trace_of_run() {
+        let trace = r#"00:00:00.000  -      000016      EXTRA  0001, 0011
+00:00:00.000  -      >>
+00:00:00.000  -        acc:      0001   Undefined 0o00000000
+00:00:00.000  -      <<
+00:00:00.000  -
+
+Memory
+
+00:00:00.000  -      000000  IWord 0o00000000 0
+00:00:00.000  -      000001  IWord 0o00000001 1
+00:00:00.000  -      000002  SWord 0o00000000
+00:00:00.000  -      000016  PWord 0o77100013 EXTRA  0001, 0011
+00:00:00.000  -      000017  PWord 0o77100017 EXTRA  0001, 0015
+00:00:00.000  -      000018  SWord 0o23212501 SQUA
+00:00:00.000  -      000019  SWord 0o00000675 F
+00:00:00.000  -      000020  PWord 0o77000012 EXTRA  0000, 0010"#;
+        let image = ObjectImage::from_memory_dump(trace).unwrap();
+        let lines = disassemble(&image).unwrap();
+        assert_eq!(
+            lines,
+            vec![
+                "; Entry 0016",
+                "        ORIGIN  16",
+                "        LINE    1",
+                "        CAPN    1",
+                "        \"SQUA\"",
+                "        \"F \"",
+                "        STOP    0",
+            ]
+        );
+    }
+
+    #[test]
+    fn fails_when_s_word_has_no_text() {
+        let dump = r#"Memory
+00:00:00.000  -      000008  SWord 0o00000000
+00:00:00.000  -      000009  SWord 0o00000052 .
+00:00:00.000  -      000010  SWord 0o01000002 AB"#;
+        let image = ObjectImage::from_memory_dump(dump).unwrap();
+        let result = disassemble(&image);
+        assert_eq!(
+            result,
+            Err(Error::FailedToDisassemble(vec![
+                "Location 0008: SWord 0o00000000 cannot be written as source".into(),
+                "Location 0010: SWord 0o01000002 cannot be written as source".into(),
+            ]))
+        );
+    }
+}
@@ -1,55 +1,57 @@
 use super::assembly::Assembly;
+use super::devices::{Channel, DeviceTable};
 use super::memory::{
     instruction_to_word, word_to_instruction, Address, Instruction, InstructionBuilder,
-    MemoryIndex, MEMORY_SIZE, *,
+    MemoryIndex, *,
 };
+use super::object::ObjectImage;
 use super::result::{Error, Result};
 
 use num_enum::TryFromPrimitive;
 
-use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fs::OpenOptions;
-use std::io::{self, Read, Write};
+use std::io::Write;
 use std::path::Path;
-use std::rc::Rc;
 
 pub struct Executor<'a> {
     ec: ExecutionContext,
+    memory_config: MemoryConfig,
     halted: bool,
-    stdin: Rc<RefCell<dyn Read>>,
-    stdout: Rc<RefCell<dyn Write>>,
+    devices: DeviceTable,
+    input: Channel,
+    output: Channel,
+    fault: Option<Error>,
     trace: Option<&'a Path>,
 }
 
 impl<'a> Executor<'a> {
     pub fn new(trace: Option<&'a Path>) -> Self {
-        let stdin = Rc::new(RefCell::new(io::stdin()));
-        let stdout = Rc::new(RefCell::new(io::stdout()));
-        Self::with_io(stdin, stdout, trace)
-    }
-
-    pub fn with_io<R, W>(
-        stdin: Rc<RefCell<R>>,
-        stdout: Rc<RefCell<W>>,
-        trace: Option<&'a Path>,
-    ) -> Self
-    where
-        R: Read + 'static,
-        W: Write + 'static,
-    {
         Self {
             ec: ExecutionContext::default(),
+            memory_config: MemoryConfig::default(),
             halted: false,
-            stdin,
-            stdout,
+            devices: DeviceTable::console(),
+            input: Channel::Input,
+            output: Channel::Output,
+            fault: None,
             trace,
         }
     }
 
+    pub fn with_devices(mut self, devices: DeviceTable) -> Self {
+        self.devices = devices;
+        self
+    }
+
+    pub fn with_memory_config(mut self, memory_config: MemoryConfig) -> Self {
+        self.memory_config = memory_config;
+        self
+    }
+
     fn trace(&self, text: &str) {
         if let Some(path) = self.trace {
-            let format = time::format_description::parse(
+            let format = time::format_description::parse_borrowed::<2>(
                 "[year]-[month]-[day] [hour repr:24]:[minute]:[second]:[subsecond digits:9]+[offset_hour]:[offset_minute]",
             )
             .unwrap();
@@ -90,21 +92,61 @@ impl<'a> Executor<'a> {
     fn trace_memory(&self) {
         self.trace("\n\nMemory\n");
         let memory = &self.ec.memory;
-        let memory =
-            (0..MEMORY_SIZE).filter_map(|i| (!memory[i].is_undefined()).then_some((i, memory[i])));
+        let memory = (0..memory.size())
+            .filter_map(|i| (!memory[i].is_undefined()).then_some((i, memory[i])));
         memory.for_each(|(i, w)| self.trace(&format!("{:>06}  {}", i, w)));
     }
 
+    #[cfg(test)]
     pub fn execute(mut self, assembly: &Assembly) -> Result<ExecutionContext> {
-        self.ec = assembly.clone().try_into()?;
+        self.ec = ExecutionContext::new(assembly.clone(), &self.memory_config)?;
+        self.run()
+    }
+
+    /// Run a program loaded from an object image, without assembling it.
+    pub fn execute_image(mut self, image: &ObjectImage) -> Result<ExecutionContext> {
+        self.ec = ExecutionContext::from_image(image, &self.memory_config)?;
+        self.run()
+    }
+
+    fn run(mut self) -> Result<ExecutionContext> {
         self.halted = false;
         while self.can_step() && !self.halted {
             self.step()?;
         }
         self.trace_memory();
+        self.devices
+            .flush_all()
+            .map_err(|err| Error::DeviceFault(err.to_string()))?;
         Ok(self.ec.clone())
     }
 
+    // A device that fails, or is not attached, halts the program with a fault
+    // that is returned once the current instruction is complete.
+    fn fault(&mut self, error: String) {
+        self.halted = true;
+        self.fault.get_or_insert(Error::DeviceFault(error));
+    }
+
+    fn read_char(&mut self) -> Option<u8> {
+        self.devices.read_char(self.input).unwrap_or_else(|err| {
+            self.fault(err.to_string());
+            None
+        })
+    }
+
+    fn write_chars(&mut self, chars: &[u8]) {
+        if let Err(err) = self.devices.write_chars(self.output, chars) {
+            self.fault(err.to_string());
+        }
+    }
+
+    fn flush(&mut self) {
+        if let Err(err) = self.devices.flush(self.output) {
+            self.fault(err.to_string());
+        }
+    }
+
     fn can_step(&self) -> bool {
         let context = &self.ec;
         let content = &context[context.pc];
@@ -123,7 +165,7 @@ impl<'a> Executor<'a> {
         self.step_word(&instruction.clone());
         self.trace_detail("<<", &instruction);
 
-        Ok(())
+        self.fault.take().map_or(Ok(()), Err)
     }
 
     fn step_word(&mut self, instruction: &Instruction) {
@@ -297,8 +339,7 @@ impl<'a> Executor<'a> {
     fn exec_tout(&mut self, instruction: &Instruction) {
         let (_acc, _, operand) = self.extract_operands(instruction);
         let chars = vec![operand.as_char().expect("TOUT invalid operand")];
-        let mut stdout = (*self.stdout).borrow_mut();
-        stdout.write_all(&chars).unwrap();
+        self.write_chars(&chars);
     }
 
     fn exec_skip(&mut self, _instruction: &Instruction) {
@@ -483,25 +524,18 @@ impl<'a> Executor<'a> {
     fn exec_pin(&mut self, instruction: &Instruction) {
         let (_acc, address, _) = self.extract_operands(instruction);
 
-        let mut stdin = (*self.stdin).borrow_mut();
-        let mut stdout = (*self.stdout).borrow_mut();
-
-        let mut buffer = vec![0u8; 1];
-
-        match stdin.read(&mut buffer) {
-            Ok(0) => {
-                stdout.write_all("DATA*".as_bytes()).unwrap();
+        match self.read_char() {
+            None => {
+                self.write_chars("DATA*".as_bytes());
             }
-            Ok(_) => {
+            Some(char) => {
                 // TODO: May not want to echo here, but just set `self.ec[address]`
-                stdout.write_all(&buffer).unwrap();
+                let buffer = vec![char];
+                self.write_chars(&buffer);
                 self.ec[address] = String::from_utf8(buffer)
                     .map(|s| s.as_str().try_into().unwrap())
                     .unwrap();
             }
-            Err(e) => {
-                panic!("Error reading from stdin: {}", e);
-            }
         }
     }
 
@@ -617,13 +651,15 @@ impl<'a> Executor<'a> {
             Function::FRAC => self.exec_extra_frac(instruction),
             Function::FLOAT => self.exec_extra_float(instruction),
             Function::CAPN => self.exec_extra_capn(instruction),
+            Function::PAGE => self.exec_extra_page(instruction),
+            Function::READER => self.exec_extra_reader(instruction),
+            Function::PUNCH => self.exec_extra_punch(instruction),
             Function::LN
             | Function::EXP
             | Function::SIN
             | Function::COS
             | Function::TAN
             | Function::ATN
-            | Function::PAGE
             | Function::RND
             | Function::ABS => unimplemented!("Unsupported {:?}", function),
             other => panic!("Invalid EXTRA code {:?}", other),
@@ -649,23 +685,18 @@ impl<'a> Executor<'a> {
     }
 
     fn exec_extra_read(&mut self, instruction: &Instruction) {
-        let mut stdout = (*self.stdout).borrow_mut();
-        stdout.flush().expect("stdout not flushed");
-
-        let mut stdin = (*self.stdin).borrow_mut();
+        self.flush();
 
         let acc = instruction.accumulator();
 
-        let mut buffer = [0u8; 1];
         let mut result = String::new();
 
         if self.ec.quote_marker {
             // Read until 4 characters, a newline, or a quote
             while result.len() < 4 {
-                if stdin.read(&mut buffer).unwrap_or(0) == 0 {
+                let Some(c) = self.read_char().map(char::from) else {
                     break;
-                }
-                let c = buffer[0] as char;
+                };
 
                 if c == '\n' {
                     break;
@@ -682,8 +713,7 @@ impl<'a> Executor<'a> {
             return;
         }
 
-        while stdin.read(&mut buffer).is_ok() {
-            let c = buffer[0] as char;
+        while let Some(c) = self.read_char().map(char::from) {
             if !c.is_whitespace() && c != ',' {
                 result.push(c);
                 break;
@@ -694,10 +724,9 @@ impl<'a> Executor<'a> {
             if first_char.is_alphabetic() {
                 // Read up to 4 more alphanumeric characters
                 while result.len() < 5 {
-                    if stdin.read(&mut buffer).unwrap_or(0) == 0 {
+                    let Some(c) = self.read_char().map(char::from) else {
                         break;
-                    }
-                    let c = buffer[0] as char;
+                    };
                     if c.is_alphanumeric() {
                         result.push(c);
                     } else {
@@ -708,8 +737,7 @@ impl<'a> Executor<'a> {
                 return;
             } else if first_char.is_numeric() || "+-.".contains(first_char) {
                 // Read numeric characters, including '@' for exponential
-                while stdin.read(&mut buffer).is_ok() {
-                    let c = buffer[0] as char;
+                while let Some(c) = self.read_char().map(char::from) {
                     if !"+-0123456789.@".contains(c) {
                         break;
                     }
@@ -750,10 +778,7 @@ impl<'a> Executor<'a> {
             3 => word_to_instruction(&word).unwrap().to_string(),
             _ => panic!("Unexpected word_type value"),
         };
-        let text = text.as_bytes();
-
-        let mut stdout = (*self.stdout).borrow_mut();
-        stdout.write_all(text).expect("stdout write error");
+        self.write_chars(text.as_bytes());
     }
 
     fn exec_extra_stop(&mut self, _instruction: &Instruction) {
@@ -761,9 +786,8 @@ impl<'a> Executor<'a> {
     }
 
     fn exec_extra_line(&mut self, _instruction: &Instruction) {
-        let mut stdout = (*self.stdout).borrow_mut();
         let newline = vec![b'\n'];
-        stdout.write_all(&newline).expect("stdout write error");
+        self.write_chars(&newline);
     }
 
     fn exec_extra_int(&mut self, instruction: &Instruction) {
@@ -782,25 +806,63 @@ impl<'a> Executor<'a> {
     }
 
     fn exec_extra_capn(&mut self, _instruction: &Instruction) {
-        let mut stdout = (*self.stdout).borrow_mut();
         while self.ec[self.ec.pc].is_sword() {
             let chars = self.ec[self.ec.pc]
                 .as_string()
                 .expect("CAPN invalid operand");
-            stdout
-                .write_all(chars.as_bytes())
-                .expect("stdout write error");
+            self.write_chars(chars.as_bytes());
             self.ec.pc += 1;
         }
     }
+
+    fn exec_extra_page(&mut self, _instruction: &Instruction) {
+        let form_feed = vec![b'\x0c'];
+        self.write_chars(&form_feed);
+    }
+
+    // READER 1 selects the paper tape reader, and READER 0 the input device.
+    fn exec_extra_reader(&mut self, instruction: &Instruction) {
+        match instruction.accumulator().memory_index() {
+            0 => self.input = Channel::Input,
+            1 => {
+                if let Some(channel) = self.select(Channel::Reader) {
+                    self.input = channel;
+                }
+            }
+            other => self.fault(format!("READER: invalid device {}", other)),
+        }
+    }
+
+    // PUNCH 1 selects the paper tape punch, and PUNCH 0 the output device.
+    fn exec_extra_punch(&mut self, instruction: &Instruction) {
+        self.flush();
+        match instruction.accumulator().memory_index() {
+            0 => self.output = Channel::Output,
+            1 => {
+                if let Some(channel) = self.select(Channel::Punch) {
+                    self.output = channel;
+                }
+            }
+            other => self.fault(format!("PUNCH: invalid device {}", other)),
+        }
+    }
+
+    fn select(&mut self, channel: Channel) -> Option<Channel> {
+        match self.devices.attached(channel) {
+            Ok(()) => Some(channel),
+            Err(err) => {
+                self.fault(err.to_string());
+                None
+            }
+        }
+    }
 }
 
 impl std::fmt::Debug for Executor<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Executor")
             .field("execution_context", &self.ec)
-            .field("stdin", &"Box<std::io::Read>")
-            .field("stdout", &"Box<std::io::Write>")
+            .field("devices", &self.devices)
             .finish()
     }
 }
@@ -855,13 +917,13 @@ impl ExecutionContext {
     }
 }
 
-impl TryFrom<Assembly> for ExecutionContext {
-    type Error = Error;
-
-    fn try_from(value: Assembly) -> std::result::Result<Self, Self::Error> {
-        let value = value.allocate_storage_locations();
-        let program_counter = value.first_pword_location().unwrap_or(0);
-        let memory = Memory::try_from(value)
+impl ExecutionContext {
+    pub fn new(assembly: Assembly, config: &MemoryConfig) -> Result<Self> {
+        let assembly = assembly
+            .allocate_storage(config.size())
+            .map_err(|err| Error::FailedToCreateExecutionContext(err.to_string()))?;
+        let program_counter = assembly.first_pword_location().unwrap_or(0);
+        let memory = Memory::load(assembly, config)
             .map_err(|err| Error::FailedToCreateExecutionContext(err.to_string()))?;
 
         Ok(Self {
@@ -872,6 +934,30 @@ impl TryFrom<Assembly> for ExecutionContext {
     }
 }
 
+impl ExecutionContext {
+    pub fn from_image(image: &ObjectImage, config: &MemoryConfig) -> Result<Self> {
+        let memory = Memory::load_words(image.words(), config)
+            .map_err(|err| Error::FailedToCreateExecutionContext(err.to_string()))?;
+        let program_counter: Address = image.entry().try_into().map_err(|_| {
+            Error::FailedToCreateExecutionContext(format!("invalid entry point {}", image.entry()))
+        })?;
+
+        Ok(Self {
+            pc: program_counter,
+            quote_marker: false,
+            memory,
+        })
+    }
+}
+
+impl TryFrom<Assembly> for ExecutionContext {
+    type Error = Error;
+
+    fn try_from(value: Assembly) -> std::result::Result<Self, Self::Error> {
+        ExecutionContext::new(value, &MemoryConfig::default())
+    }
+}
+
 impl std::ops::Index<usize> for ExecutionContext {
     type Output = Word;
 
@@ -929,13 +1015,17 @@ impl std::ops::IndexMut<Address> for ExecutionContext {
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant, clippy::assertions_on_constants)]
 mod test {
     use pretty_assertions::assert_eq;
-    use std::io::Cursor;
+    use std::cell::RefCell;
+    use std::rc::Rc;
 
     use super::*;
 
     use crate::bbcx::assembler::*;
+    use crate::bbcx::assembly::LiteralPool;
+    use crate::bbcx::devices::Buffer;
     use crate::bbcx::parser::*;
 
     fn execute(input: &str) -> Result<ExecutionContext> {
@@ -944,19 +1034,16 @@ mod test {
     }
 
     fn execute_io(input: &str, stdin: &str, expected: &str) -> Result<ExecutionContext> {
-        let stdin = String::from(stdin);
-        let stdin_buffer = Cursor::new(stdin);
-        let stdin = Rc::new(RefCell::new(std::io::BufReader::new(stdin_buffer)));
-
-        let stdout_buffer = Vec::new();
-        let stdout = Rc::new(RefCell::new(std::io::BufWriter::new(stdout_buffer)));
+        let buffer = Rc::new(RefCell::new(Buffer::new(stdin)));
+        let devices = DeviceTable::default()
+            .with_device(Channel::Input, buffer.clone())
+            .with_device(Channel::Output, buffer.clone());
 
-        let executor = Executor::with_io(stdin, stdout.clone(), None);
+        let executor = Executor::new(None).with_devices(devices);
         let ec = do_execute(input, executor).unwrap().clone();
 
-        let stdout = stdout.borrow();
-        let bytes = stdout.buffer();
-        let actual = String::from_utf8_lossy(&bytes);
+        let buffer = buffer.borrow();
+        let actual = String::from_utf8_lossy(buffer.output());
 
         assert_eq!(actual, String::from(expected));
 
@@ -970,11 +1057,14 @@ mod test {
             .map(Parser::parse_line)
             .filter_map(|l| l.ok())
             .collect::<Vec<_>>();
-        let assembly =
-            Assembler::assemble(&program).expect(&format!("Failed to assemble {}", input));
+        // Expected memory layouts give each literal operand its own word.
+        let assembly = Assembler::default()
+            .with_literal_pool(LiteralPool::PerUse)
+            .build(&program)
+            .unwrap_or_else(|_| panic!("Failed to assemble {}", input));
         let ec = executor
             .execute(&assembly)
-            .expect(&format!("Failed to execute {}", input));
+            .unwrap_or_else(|_| panic!("Failed to execute {}", input));
         Ok(ec.clone())
     }
 
@@ -3483,6 +3573,43 @@ mod test {
         test_result(&actual, &expected)
     }
 
+    #[test]
+    fn test_extra_read_at_end_of_input() {
+        let program = r#"
+0100    EXTRA   1, 4
+0101    READ    3
+0102    READ    5
+"#;
+        let actual = execute_io(program, "12", "").ok().unwrap();
+        let expected = ExecutionContext::default()
+            .with_instruction(
+                100,
+                InstructionBuilder::new(Function::EXTRA)
+                    .with_accumulator(1)
+                    .with_address(4)
+                    .build(),
+            )
+            .with_instruction(
+                101,
+                InstructionBuilder::new(Function::EXTRA)
+                    .with_accumulator(3)
+                    .with_address(4)
+                    .build(),
+            )
+            .with_instruction(
+                102,
+                InstructionBuilder::new(Function::EXTRA)
+                    .with_accumulator(5)
+                    .with_address(4)
+                    .build(),
+            )
+            .with_memory_word(1, 12)
+            .with_memory_word(3, "")
+            .with_memory_word(5, "")
+            .with_program_counter(103);
+        test_result(&actual, &expected)
+    }
+
     #[test]
     fn test_extra_print() {
         let program = r#"
@@ -3671,9 +3798,21 @@ mod test {
     }
 
     #[test]
-    #[ignore]
     fn test_extra_page() {
-        // Will implement if required.
+        let program = r#"
+0100    PAGE
+"#;
+        let actual = execute_io(program, "", "\x0c").ok().unwrap();
+        let expected = ExecutionContext::default()
+            .with_instruction(
+                100,
+                InstructionBuilder::new(Function::EXTRA)
+                    .with_accumulator(0)
+                    .with_address(16)
+                    .build(),
+            )
+            .with_program_counter(101);
+        test_result(&actual, &expected)
     }
 
     #[test]
@@ -3687,4 +3826,164 @@ mod test {
     fn test_extra_abs() {
         // Will implement if required.
     }
+
+    #[test]
+    fn test_extra_reader_and_punch() {
+        let program = r#"
+0100    READER  1
+0101    READ    1
+0102    READER  0
+0103    READ    2
+0104    PUNCH   1
+0105    PRINT   2
+0106    PUNCH   0
+0107    PRINT   1
+"#;
+        let console = Rc::new(RefCell::new(Buffer::new("7")));
+        let tape = Rc::new(RefCell::new(Buffer::new("42")));
+        let devices = DeviceTable::default()
+            .with_device(Channel::Input, console.clone())
+            .with_device(Channel::Output, console.clone())
+            .with_device(Channel::Reader, tape.clone())
+            .with_device(Channel::Punch, tape.clone());
+
+        let executor = Executor::new(None).with_devices(devices);
+        let actual = do_execute(program, executor).unwrap();
+
+        assert_eq!(actual[Accumulator::try_from(1).unwrap()].as_i64(), Ok(42));
+        assert_eq!(actual[Accumulator::try_from(2).unwrap()].as_i64(), Ok(7));
+        assert_eq!(console.borrow().output(), b"      42 ");
+        assert_eq!(tape.borrow().output(), b"       7 ");
+    }
+
+    #[test]
+    fn fails_when_selected_device_is_not_attached() {
+        for (program, channel) in [
+            ("0100    READER  1", "Reader"),
+            ("0100    PUNCH   1", "Punch"),
+        ] {
+            let program = vec![Parser::parse_line(program).unwrap()];
+            let assembly = Assembler::assemble(&program).unwrap();
+            let devices = DeviceTable::default()
+                .with_device(Channel::Input, Rc::new(RefCell::new(Buffer::default())))
+                .with_device(Channel::Output, Rc::new(RefCell::new(Buffer::default())));
+            let result = Executor::new(None).with_devices(devices).execute(&assembly);
+            assert_eq!(
+                result.err(),
+                Some(Error::DeviceFault(format!(
+                    "no device attached to {} channel",
+                    channel
+                )))
+            );
+        }
+    }
+
+    fn execute_with_memory(input: &str, config: MemoryConfig) -> Result<ExecutionContext> {
+        let executor = Executor::new(None).with_memory_config(config);
+        do_execute(input, executor)
+    }
+
+    #[test]
+    fn literals_allocated_from_top_of_configured_memory() {
+        let program = r#"
+0100    TAKE 1, +42
+"#;
+        let config = MemoryConfig::new(512, MemoryFill::Undefined).unwrap();
+        let actual = execute_with_memory(program, config).ok().unwrap();
+        assert_eq!(actual.memory.size(), 512);
+        assert_eq!(actual[1], 42.try_into().unwrap());
+        assert_eq!(actual[511], 42.try_into().unwrap());
+        assert!(actual[510].is_undefined());
+    }
+
+    #[test]
+    fn identical_literals_share_storage() {
+        let program = r#"
+0100    TAKE 1, +42
+0101    ADD 1, +42
+"#;
+        let program = program
+            .lines()
+            .map(Parser::parse_line)
+            .filter_map(|l| l.ok())
+            .collect::<Vec<_>>();
+        let assembly = Assembler::assemble(&program).unwrap();
+        let actual = Executor::new(None).execute(&assembly).unwrap();
+        assert_eq!(actual[1], 84.try_into().unwrap());
+        assert_eq!(actual[MEMORY_SIZE - 1], 42.try_into().unwrap());
+        assert!(actual[MEMORY_SIZE - 2].is_undefined());
+    }
+
+    #[test]
+    fn standard_library_formats_numbers() {
+        let program = r#"
+        ORIGIN  16
+        TAKE    1, +1974
+        TAKE    3, +0
+        JUMP    7, PDEC
+        TAKE    1, -42
+        TAKE    3, +5
+        JUMP    7, PDEC
+        TAKE    1, +255
+        TAKE    3, +4
+        JUMP    7, PHEX
+        TAKE    1, +10
+        TAKE    3, +0
+        JUMP    7, PBIN
+        STOP
+"#;
+        let program = format!("{}{}", program, include_str!("stdlib/numbers.bbc"));
+        let _ = execute_io(&program, "", "1974  -42  FF1010");
+    }
+
+    #[test]
+    fn unused_memory_filled_with_zero() {
+        let program = r#"
+0100    TAKE 1, +42
+"#;
+        let config = MemoryConfig::new(256, MemoryFill::Zero).unwrap();
+        let actual = execute_with_memory(program, config).ok().unwrap();
+        assert_eq!(actual[200], 0.try_into().unwrap());
+        assert_eq!(actual[255], 42.try_into().unwrap());
+        assert!(actual.memory.iter().all(|w| !w.is_undefined()));
+    }
+
+    #[test]
+    fn unused_memory_filled_with_poison() {
+        let program = r#"
+0100    TAKE 1, 200
+"#;
+        let config = MemoryConfig::new(256, MemoryFill::Poison).unwrap();
+        let actual = execute_with_memory(program, config).ok().unwrap();
+        assert_eq!(actual[200], Word::new(WordType::IWord, 0o5252_5252));
+        assert_eq!(actual[1], actual[200]);
+    }
+
+    #[test]
+    fn fails_when_program_exceeds_configured_memory() {
+        let program = r#"
+0100    NIL
+0300    +42
+"#;
+        let program = program
+            .lines()
+            .map(Parser::parse_line)
+            .filter_map(|l| l.ok())
+            .collect::<Vec<_>>();
+        let assembly = Assembler::assemble(&program).unwrap();
+        let config = MemoryConfig::new(256, MemoryFill::Undefined).unwrap();
+        let result = Executor::new(None)
+            .with_memory_config(config)
+            .execute(&assembly);
+        assert!(matches!(
+            result,
+            Err(Error::FailedToCreateExecutionContext(_))
+        ));
+    }
+
+    #[test]
+    fn memory_config_rejects_invalid_size() {
+        assert!(MemoryConfig::new(4, MemoryFill::Undefined).is_err());
+        assert!(MemoryConfig::new(2048, MemoryFill::Undefined).is_err());
+    }
 }
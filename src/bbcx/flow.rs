@@ -0,0 +1,312 @@
+use super::assembler::DEFAULT_ORIGIN;
+use super::assembly::Location;
+use super::ast::Mnemonic;
+use super::disassembler::{has_address_operand, instruction, library_routine};
+use super::memory::{Instruction, MemoryIndex, Word, WordType};
+use super::object::ObjectImage;
+
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+
+// ****************************************************************************
+// The flow of control through a memory image, found without running it. Each
+// instruction passes control to the next word, unless it is one of:
+//
+//     JUMP                always to its operand; with accumulator 7, it is a call
+//                         that returns to the next word
+//     JEZ ... JZI         to its operand when the condition holds
+//     SKIP                always past the next word
+//     SKAE ... SKEI       past the next word when the condition holds
+//     EXTRA STOP          nowhere
+//     EXTRA CAPN          past the S-words that follow it
+//
+// An indirect operand is followed through a pointer word that no instruction
+// stores to by name. An indirect jump through an accumulator is taken to be a
+// return from a call. Any other indirect or indexed jump goes to an unknown
+// location.
+
+/// Where control passes to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Target {
+    Location(Location),
+    Return,
+    Unknown,
+}
+
+/// A transfer of control from an instruction. A jump is taken by the operand of
+/// the instruction, rather than by moving on through memory. A conditional
+/// transfer is taken when its condition holds, or does not.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Edge {
+    pub target: Target,
+    pub jump: bool,
+    pub condition: Option<bool>,
+}
+
+impl Edge {
+    fn next(location: Location, condition: Option<bool>) -> Self {
+        let target = Target::Location(location);
+        Self {
+            target,
+            jump: false,
+            condition,
+        }
+    }
+
+    fn jump(target: Target, condition: Option<bool>) -> Self {
+        Self {
+            target,
+            jump: true,
+            condition,
+        }
+    }
+}
+
+pub struct Flow {
+    words: BTreeMap<Location, Word>,
+    entry: Location,
+    memory_size: usize,
+    written: HashSet<Location>,
+    unknown_writes: bool,
+    data: HashSet<Location>,
+}
+
+impl Flow {
+    pub fn new(image: &ObjectImage, memory_size: usize) -> Self {
+        let words = image.words().iter().copied().collect::<BTreeMap<_, _>>();
+        let mut flow = Self {
+            words,
+            entry: image.entry(),
+            memory_size,
+            written: HashSet::new(),
+            unknown_writes: false,
+            data: HashSet::new(),
+        };
+        flow.find_writes();
+        flow.data = flow
+            .instructions()
+            .filter(|(_, instruction)| {
+                let function = instruction.function();
+                instruction.is_indirect() || reads(function) || writes(function)
+            })
+            .map(|(_, instruction)| instruction.address().memory_index())
+            .collect();
+        flow
+    }
+
+    pub fn entry(&self) -> Location {
+        self.entry
+    }
+
+    pub fn word(&self, location: Location) -> Option<&Word> {
+        self.words.get(&location)
+    }
+
+    pub fn instruction(&self, location: Location) -> Option<Instruction> {
+        self.word(location).and_then(instruction)
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = (Location, Instruction)> + '_ {
+        self.words
+            .iter()
+            .filter_map(|(location, word)| instruction(word).map(|i| (*location, i)))
+    }
+
+    pub fn is_instruction(&self, location: Location) -> bool {
+        self.word(location)
+            .is_some_and(|word| word.content_type() == WordType::PWord)
+    }
+
+    /// Whether a location may be changed by the program, rather than only
+    /// holding the word it was loaded with.
+    pub fn is_written(&self, location: Location) -> bool {
+        self.unknown_writes || self.written.contains(&location)
+    }
+
+    /// Whether a location is used as a value or a pointer by an instruction,
+    /// rather than being executed.
+    pub fn is_data(&self, location: Location) -> bool {
+        self.data.contains(&location)
+    }
+
+    /// Whether every store made by the program is to a known location.
+    pub fn has_known_writes(&self) -> bool {
+        !self.unknown_writes
+    }
+
+    /// The location used as the operand of an instruction, when it can be known.
+    pub fn operand(&self, instruction: &Instruction) -> Target {
+        let address = instruction.address().memory_index();
+        if instruction.index_register().is_indexable() {
+            Target::Unknown
+        } else if !instruction.is_indirect() {
+            Target::Location(address)
+        } else if address < DEFAULT_ORIGIN {
+            Target::Return
+        } else {
+            self.pointer(address)
+                .map_or(Target::Unknown, Target::Location)
+        }
+    }
+
+    // The location held by a pointer word that the program does not change.
+    fn pointer(&self, address: Location) -> Option<Location> {
+        (!self.written.contains(&address))
+            .then(|| self.instruction(address))
+            .flatten()
+            .map(|pointer| pointer.address().memory_index())
+    }
+
+    /// The transfers of control from the instruction at a location.
+    pub fn edges(&self, location: Location, instruction: &Instruction) -> Vec<Edge> {
+        let next = location + 1;
+        match instruction.function() {
+            Mnemonic::JUMP if instruction.accumulator().memory_index() == 7 => vec![
+                Edge::jump(self.operand(instruction), None),
+                Edge::next(next, None),
+            ],
+            Mnemonic::JUMP => vec![Edge::jump(self.operand(instruction), None)],
+            function if is_conditional_jump(function) => vec![
+                Edge::jump(self.operand(instruction), Some(true)),
+                Edge::next(next, Some(false)),
+            ],
+            Mnemonic::SKIP => vec![Edge::next(next + 1, None)],
+            function if is_conditional_skip(function) => vec![
+                Edge::next(next + 1, Some(true)),
+                Edge::next(next, Some(false)),
+            ],
+            Mnemonic::EXTRA => match library_routine(instruction) {
+                Some(Mnemonic::STOP) => vec![],
+                Some(Mnemonic::CAPN) => {
+                    let past_text = (next..)
+                        .find(|location| !self.word(*location).is_some_and(Word::is_sword))
+                        .unwrap_or(next);
+                    vec![Edge::next(past_text, None)]
+                }
+                _ => vec![Edge::next(next, None)],
+            },
+            _ => vec![Edge::next(next, None)],
+        }
+    }
+
+    /// The instructions that can be reached from the entry. When a jump goes to
+    /// an unknown location, every instruction that the program refers to, other
+    /// than as data, is taken to be reachable too.
+    pub fn reachable(&self) -> BTreeSet<Location> {
+        let (reached, is_complete) = self.reach(vec![self.entry]);
+        if is_complete {
+            return reached;
+        }
+
+        let mut roots = self
+            .words
+            .values()
+            .filter_map(instruction)
+            .filter(has_address_operand)
+            .map(|instruction| instruction.address().memory_index())
+            .filter(|location| !self.is_data(*location))
+            .collect::<Vec<_>>();
+        roots.push(self.entry);
+        self.reach(roots).0
+    }
+
+    fn reach(&self, roots: Vec<Location>) -> (BTreeSet<Location>, bool) {
+        let mut reached = BTreeSet::new();
+        let mut is_complete = true;
+        let mut pending = roots;
+        while let Some(location) = pending.pop() {
+            if !self.is_instruction(location) || !reached.insert(location) {
+                continue;
+            }
+            let Some(instruction) = self.instruction(location) else {
+                continue;
+            };
+            if instruction.function() == Mnemonic::EXEC {
+                match self.operand(&instruction) {
+                    Target::Location(executed) => pending.push(executed),
+                    _ => is_complete = false,
+                }
+            }
+            for edge in self.edges(location, &instruction) {
+                match edge.target {
+                    Target::Location(target) => pending.push(target),
+                    Target::Return => {}
+                    Target::Unknown => is_complete = false,
+                }
+            }
+        }
+        (reached, is_complete)
+    }
+
+    // An indexed store may be to any location from its operand up to the next
+    // instruction.
+    fn find_writes(&mut self) {
+        let stores = self
+            .words
+            .values()
+            .filter_map(instruction)
+            .filter(|instruction| writes(instruction.function()))
+            .collect::<Vec<_>>();
+
+        let direct = stores
+            .iter()
+            .filter(|instruction| !instruction.is_indirect())
+            .map(|instruction| (instruction.address().memory_index(), instruction))
+            .collect::<Vec<_>>();
+        self.mark_writes(&direct);
+
+        let mut indirect = Vec::new();
+        for instruction in stores
+            .iter()
+            .filter(|instruction| instruction.is_indirect())
+        {
+            let address = instruction.address().memory_index();
+            match self.pointer(address) {
+                Some(location) if address >= DEFAULT_ORIGIN => {
+                    indirect.push((location, instruction))
+                }
+                _ => self.unknown_writes = true,
+            }
+        }
+        self.mark_writes(&indirect);
+    }
+
+    fn mark_writes(&mut self, stores: &[(Location, &Instruction)]) {
+        for (location, instruction) in stores {
+            if instruction.index_register().is_indexable() {
+                let end = self
+                    .words
+                    .range(location + 1..)
+                    .find(|(_, word)| word.content_type() == WordType::PWord)
+                    .map_or(self.memory_size, |(end, _)| *end);
+                self.written.extend(*location..end);
+            } else {
+                self.written.insert(*location);
+            }
+        }
+    }
+}
+
+/// Whether an instruction stores to its operand.
+pub(super) fn writes(function: Mnemonic) -> bool {
+    (Mnemonic::NILX as u32..=Mnemonic::PIN as u32).contains(&(function as u32))
+        || matches!(function, Mnemonic::DECR | Mnemonic::INCR)
+}
+
+/// Whether an instruction uses the word at its operand, rather than the address.
+pub(super) fn reads(function: Mnemonic) -> bool {
+    let arithmetic = (Mnemonic::OR as u32..=Mnemonic::DDIV as u32).contains(&(function as u32))
+        && !matches!(
+            function,
+            Mnemonic::SKIP | Mnemonic::SHL | Mnemonic::ROT | Mnemonic::DSHL | Mnemonic::DROT
+        );
+    let exchange = (Mnemonic::NILX as u32..=Mnemonic::DVDX as u32).contains(&(function as u32));
+    arithmetic || exchange || matches!(function, Mnemonic::DECR | Mnemonic::INCR | Mnemonic::EXEC)
+}
+
+fn is_conditional_jump(function: Mnemonic) -> bool {
+    (Mnemonic::JEZ as u32..=Mnemonic::JZI as u32).contains(&(function as u32))
+}
+
+fn is_conditional_skip(function: Mnemonic) -> bool {
+    (Mnemonic::SKAE as u32..=Mnemonic::SKEI as u32).contains(&(function as u32))
+}
@@ -0,0 +1,191 @@
+use super::ast::SourceLine;
+use super::macros::{ExpandedLine, Fields, Origin};
+use super::parser::Parser;
+
+use crate::result::{Error, Result};
+
+// ****************************************************************************
+// Source is formatted into the columns of a listing: location, label, word or
+// directive, then comment. A line holding only a comment keeps it after the
+// location. Macro definitions, invocations and INCLUDE lines are not parsed,
+// so are split into the same columns with their operands kept as written. A
+// body line that does parse, because it uses no parameter in the place of an
+// accumulator or mnemonic, is formatted as source. A formatted line must be
+// read back as the line it was made from, so formatting never changes the
+// program.
+
+/// The formatted text of the lines of a file, leaving out expansions and the
+/// lines of included files.
+pub fn format(lines: &[ExpandedLine]) -> Result<Vec<String>> {
+    let mut formatted = Vec::new();
+    let mut errors = Vec::new();
+    for line in lines
+        .iter()
+        .filter(|line| line.origin() != Origin::Expansion && !line.position().is_included())
+    {
+        let text = line.text().trim_end();
+        let result = match line.origin() {
+            Origin::Source => format_line(text),
+            Origin::Definition => format_line(text).or_else(|_| format_fields(text)),
+            _ => format_fields(text),
+        };
+        match result {
+            Ok(text) => formatted.push(text),
+            Err(error) => errors.push(format!("Line {}: {}", line.position(), error)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(formatted)
+    } else {
+        Err(Error::FailedToFormat(errors))
+    }
+}
+
+fn format_line(text: &str) -> std::result::Result<String, String> {
+    if text.trim().is_empty() {
+        return Ok(String::new());
+    }
+
+    let line = Parser::parse_line(text).map_err(|error| error.to_string())?;
+    let formatted = if is_comment_only(&line) {
+        let location = line
+            .location()
+            .map_or("".to_string(), |l| format!("{:<8}", format!("{:04}", l)));
+        format!("{}{}", location, line.comment())
+    } else {
+        line.to_string().trim_end().to_string()
+    };
+
+    match Parser::parse_line(&formatted) {
+        Ok(formatted_line) if formatted_line == line => Ok(formatted),
+        _ => Err(format!("\"{}\" would not be read back the same", formatted)),
+    }
+}
+
+fn format_fields(text: &str) -> std::result::Result<String, String> {
+    let fields = Fields::from(text);
+    let location = fields
+        .location
+        .as_ref()
+        .map_or("".to_string(), |l| format!("{:04}", l));
+    let formatted = if fields.label.is_none() && fields.keyword.is_empty() {
+        format!("{:<8}{}", location, fields.comment)
+    } else {
+        let label = fields
+            .label
+            .as_ref()
+            .map_or("".to_string(), |l| format!("{}:", l));
+        format!(
+            "{:<8}{:<10}{:<8}{:<34}{}",
+            location, label, fields.keyword, fields.operands, fields.comment
+        )
+    };
+    let formatted = formatted.trim_end().to_string();
+
+    let read_back = Fields::from(formatted.as_str());
+    if (
+        &read_back.location,
+        &read_back.label,
+        read_back.keyword,
+        read_back.operands,
+        read_back.comment,
+    ) == (
+        &fields.location,
+        &fields.label,
+        fields.keyword,
+        fields.operands,
+        fields.comment,
+    ) {
+        Ok(formatted)
+    } else {
+        Err(format!("\"{}\" would not be read back the same", formatted))
+    }
+}
+
+fn is_comment_only(line: &SourceLine) -> bool {
+    line.label().name().is_none()
+        && line.source_program_word().is_none()
+        && line.directive().is_none()
+        && !line.comment().is_empty()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bbcx::ast::Position;
+    use crate::bbcx::include::SourceText;
+    use crate::bbcx::macros::MacroProcessor;
+    use pretty_assertions::assert_eq;
+
+    fn format_source(input: &str) -> Result<Vec<String>> {
+        let lines = input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| SourceText::new(line, Position::new(i + 1)))
+            .collect::<Vec<_>>();
+        format(&MacroProcessor::default().expand(&lines)?)
+    }
+
+    #[test]
+    fn will_format_into_columns() {
+        let formatted = format_source(
+            "0009    ;\"SQRT\"\n\
+             0037     \"?...\"\n\
+             0038\tEXTRA   2, 4        ; READ\n\
+             \n\
+             START: TAKE 1,*PTR+1(2)  ;FIRST\n\
+             ; NOTE\n\
+             \tTTYP 2\n\
+             0040 ADD 1, +2.0\n\
+             VAL:\t-1.0\n\
+             \tORIGIN 100",
+        )
+        .unwrap();
+        assert_eq!(
+            formatted,
+            vec![
+                "0009    ;\"SQRT\"",
+                "0037              \"?...\"",
+                "0038              EXTRA   2, 4                              ; READ",
+                "",
+                "        START:    TAKE    1, *PTR+1(2)                      ;FIRST",
+                "; NOTE",
+                "                  TTYP    2",
+                "0040              ADD     1, +2.0",
+                "        VAL:      -1.0",
+                "                  ORIGIN  100",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_format_macros_into_columns() {
+        let formatted = format_source(
+            "        MACRO   TWICE X, ACC  ; ADD TWICE\n\
+             ; BODY\n\
+             \x20       ADD 1,X\n\
+             \x20       ADD     ACC, X\n\
+             \x20       ENDM   \n\
+             HERE: TWICE   +1, 2",
+        )
+        .unwrap();
+        assert_eq!(
+            formatted,
+            vec![
+                "                  MACRO   TWICE X, ACC                      ; ADD TWICE",
+                "; BODY",
+                "                  ADD     1, X",
+                "                  ADD     ACC, X",
+                "                  ENDM",
+                "        HERE:     TWICE   +1, 2",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_not_format_invalid_lines() {
+        let result = format_source("        TAKE    1, +1\n        TAKE    9, 1");
+        assert!(matches!(result, Err(Error::FailedToFormat(errors)) if errors.len() == 1));
+    }
+}
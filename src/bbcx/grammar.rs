@@ -24,29 +24,70 @@ impl Grammar {
 // A source program line consists of the address of the location which is to
 // receive the translated version of the source word followed by the source
 // word itself.
+//
+// Added: The location may be omitted, in which case the word is placed at the
+// next address of the location counter. Blank and comment-only lines are
+// allowed.
 fn source_program_line<'a>() -> Parser<'a, SourceLine> {
-    let as_source_program_line = |(((l, lbl), w), c)| SourceLine::new(l, lbl, w, c);
+    let as_source_program_line = |(((l, lbl), s), c)| SourceLine::from_statement(l, lbl, s, c);
 
-    (location() + label() - inline_ws().opt() + source_program_word().opt() - inline_ws().opt()
+    (location().opt() + label() - inline_ws().opt() + statement().opt() - inline_ws().opt()
         + comment()
         - end())
     .map(as_source_program_line)
     .name("source_program_line")
 }
 
+// Added: ++
+fn statement<'a>() -> Parser<'a, Statement> {
+    (directive().map(Statement::Directive) | source_program_word().map(Statement::SourceWord))
+        .name("statement")
+}
+
+// <directive> ::= ORIGIN <space> <absolute address> |
+//                 EQU <space> <numeric address> |
+//                 RESERVE <space> <unsigned integer> |
+//                 TEXT <space> <quote><actual character>...<unquote> |
+//                 EXPORT <space> <identifier>, ... |
+//                 IMPORT <space> <identifier>, ...
+pub(super) fn directive<'a>() -> Parser<'a, Directive> {
+    let text = || (sym('"') * actual_character().repeat(1..) - sym('"')).map(String::from_iter);
+    let identifiers = || {
+        (identifier() + (sym(',') * inline_ws().opt() * identifier()).repeat(0..)).map(
+            |(first, rest)| {
+                let mut identifiers = vec![first];
+                identifiers.extend(rest);
+                identifiers
+            },
+        )
+    };
+
+    (((exact("ORIGIN") - inline_ws()) * absolute_address()).map(Directive::Origin)
+        | ((exact("EQU") - inline_ws()) * numeric_address()).map(Directive::Equ)
+        | ((exact("RESERVE") - inline_ws()) * unsigned_integer())
+            .map(|size| Directive::Reserve(size as usize))
+        | ((exact("TEXT") - inline_ws()) * text()).map(Directive::Text)
+        | ((exact("EXPORT") - inline_ws()) * identifiers()).map(Directive::Export)
+        | ((exact("IMPORT") - inline_ws()) * identifiers()).map(Directive::Import))
+    .name("directive")
+}
+
+pub(super) const DIRECTIVES: &[&str] = &["ORIGIN", "EQU", "RESERVE", "TEXT", "EXPORT", "IMPORT"];
+// Added: --
+
 // <location> ::= <numeric address><space>
-fn location<'a>() -> Parser<'a, Location> {
+pub(super) fn location<'a>() -> Parser<'a, Location> {
     (absolute_address() - inline_ws()).name("location")
 }
 
 // label := <idenifier> :
-fn label<'a>() -> Parser<'a, Label> {
+pub(super) fn label<'a>() -> Parser<'a, Label> {
     ((identifier() - sym(':')).map(Label::from)) | empty().map(|_| Label::from(None)).name("label")
 }
 
 // This source word can be any of the four tyoes (but certain conventions
 // must be kept to if the program is to list correctly)
-fn source_program_word<'a>() -> Parser<'a, SourceWord> {
+pub(super) fn source_program_word<'a>() -> Parser<'a, SourceWord> {
     (pword().map(SourceWord::PWord)
         | fword().map(SourceWord::FWord)
         | iword().map(SourceWord::IWord)
@@ -55,7 +96,7 @@ fn source_program_word<'a>() -> Parser<'a, SourceWord> {
 }
 
 // ; COMMENT
-fn comment<'a>() -> Parser<'a, Comment> {
+pub(super) fn comment<'a>() -> Parser<'a, Comment> {
     let actual = || {
         sym(';')
             * none_of("\n")
@@ -105,7 +146,7 @@ fn oct_dig<'a>() -> Parser<'a, char> {
 
 // Added: ++
 // Represents 'space()' in description
-fn inline_ws<'a>() -> Parser<'a, String> {
+pub(super) fn inline_ws<'a>() -> Parser<'a, String> {
     one_of(" \t")
         .repeat(1..)
         .map(String::from_iter)
@@ -113,7 +154,7 @@ fn inline_ws<'a>() -> Parser<'a, String> {
 }
 // Added: --
 
-fn store_operand<'a>() -> Parser<'a, StoreOperand> {
+pub(super) fn store_operand<'a>() -> Parser<'a, StoreOperand> {
     (address_operand().map(StoreOperand::AddressOperand)
         | const_operand().map(StoreOperand::ConstOperand)
         | empty().map(|_| StoreOperand::None))
@@ -142,12 +183,38 @@ fn simple_address_operand<'a>() -> Parser<'a, SimpleAddressOperand> {
     .name("simple_address_operand")
 }
 
-// <address> ::= <identifier> | <numeric address>
+// Added: ++
+// <address> ::= <term> | <address> + <term> | <address> - <term>
 fn address<'a>() -> Parser<'a, Address> {
-    (identifier().map(Address::Identifier) | numeric_address().map(Address::NumericAddress))
+    (term() + (one_of("+-").map(Operator::from) + term()).repeat(0..))
+        .map(|(first, rest)| fold_expression(first, rest))
         .name("address")
 }
 
+// <term> ::= <primary> | <term> * <primary> | <term> / <primary>
+fn term<'a>() -> Parser<'a, Address> {
+    (primary() + (one_of("*/").map(Operator::from) + primary()).repeat(0..))
+        .map(|(first, rest)| fold_expression(first, rest))
+        .name("term")
+}
+
+fn fold_expression(first: Address, rest: Vec<(Operator, Address)>) -> Address {
+    rest.into_iter().fold(first, |left, (operator, right)| {
+        Address::Expression(Box::new(left), operator, Box::new(right))
+    })
+}
+// Added: --
+
+// A parenthesised address overrides the usual precedence. An index register
+// can still follow the operand, as in (TABLE+1)*2(3).
+// <primary> ::= <identifier> | <numeric address> | ( <address> )
+fn primary<'a>() -> Parser<'a, Address> {
+    (identifier().map(Address::Identifier)
+        | numeric_address().map(Address::NumericAddress)
+        | (sym('(') * call(address) - sym(')')))
+    .name("primary")
+}
+
 // <identifier> ::= <alpha char.> | <identifier><alpha char.> !
 //                  <identifier><digit>
 fn identifier<'a>() -> Parser<'a, Identifier> {
@@ -237,11 +304,13 @@ fn unsigned_fword<'a>() -> Parser<'a, FWord> {
 // <decimal part> ::= <unsigned integer>.<unsigned integer> |
 //                    .<unsigned integer>
 fn decimal_part<'a>() -> Parser<'a, String> {
-    let if_to_string = |(i, f): (IntType, IntType)| format!("{}.{}", i, f);
-    let f_to_string = |f: IntType| format!("0.{}", f);
+    let if_to_string = |(i, f): (IntType, String)| format!("{}.{}", i, f);
+    let f_to_string = |f: String| format!("0.{}", f);
+    // The fraction is kept as digits, so that its leading zeros are not lost.
+    let fraction = || digit().repeat(1..).map(String::from_iter);
 
-    ((unsigned_integer() - sym('.').discard() + unsigned_integer()).map(if_to_string)
-        | (sym('.').discard() * unsigned_integer()).map(f_to_string))
+    ((unsigned_integer() - sym('.').discard() + fraction()).map(if_to_string)
+        | (sym('.').discard() * fraction()).map(f_to_string))
     .name("decimal_part")
 }
 
@@ -284,7 +353,7 @@ fn unsigned_integer<'a>() -> Parser<'a, IntType> {
 // An accumulator address (an integer from 0 to 7).
 // The accumulator address and terminating comma can be omitted
 // if the user wishes to use accumulator 1.
-fn acc<'a>() -> Parser<'a, Acc> {
+pub(super) fn acc<'a>() -> Parser<'a, Acc> {
     ((oct_dig() - sym(',')).map(Acc::from) | empty().map(|_| Acc::from(None))).name("acc")
 }
 
@@ -295,93 +364,108 @@ fn absolute_address<'a>() -> Parser<'a, AddressRef> {
         .name("absolute_address")
 }
 
-fn mnemonic<'a>() -> Parser<'a, Mnemonic> {
-    (exact("DBYTE").map(|_| Mnemonic::DBYTE)
-        | exact("EXTRA").map(|_| Mnemonic::EXTRA)
-        | exact("MOCKP").map(|_| Mnemonic::MOCKP)
-        | exact("MOCKS").map(|_| Mnemonic::MOCKS)
-        | exact("MPLYX").map(|_| Mnemonic::MULTX)
-        | exact("MULTX").map(|_| Mnemonic::MULTX)
-        | exact("NEQVX").map(|_| Mnemonic::NEQVX)
-        | exact("SUBTX").map(|_| Mnemonic::SUBTX)
-        | exact("ADDX").map(|_| Mnemonic::ADDX)
-        | exact("ANDX").map(|_| Mnemonic::ANDX)
-        | exact("DDIV").map(|_| Mnemonic::DDIV)
-        | exact("DECR").map(|_| Mnemonic::DECR)
-        | exact("DMULT").map(|_| Mnemonic::DMULT)
-        | exact("DROT").map(|_| Mnemonic::DROT)
-        | exact("DSHL").map(|_| Mnemonic::DSHL)
-        | exact("DVDX").map(|_| Mnemonic::DVDX)
-        | exact("EXEC").map(|_| Mnemonic::EXEC)
-        | exact("INCR").map(|_| Mnemonic::INCR)
-        | exact("JUMP").map(|_| Mnemonic::JUMP)
-        | exact("MPLY").map(|_| Mnemonic::MULT)
-        | exact("MULT").map(|_| Mnemonic::MULT)
-        | exact("NEQV").map(|_| Mnemonic::NEQV)
-        | exact("NTHG").map(|_| Mnemonic::NIL)
-        | exact("PNEG").map(|_| Mnemonic::PNEG)
-        | exact("PNOT").map(|_| Mnemonic::PNOT)
-        | exact("POWR").map(|_| Mnemonic::POWR)
-        | exact("PSQU").map(|_| Mnemonic::PSQU)
-        | exact("PTYP").map(|_| Mnemonic::PTYP)
-        | exact("PTYZ").map(|_| Mnemonic::PTYZ)
-        | exact("PPPP").map(|_| Mnemonic::PPPP)
-        | exact("SKAE").map(|_| Mnemonic::SKAE)
-        | exact("SKAL").map(|_| Mnemonic::SKAL)
-        | exact("SKAG").map(|_| Mnemonic::SKAG)
-        | exact("SKAN").map(|_| Mnemonic::SKAN)
-        | exact("SKED").map(|_| Mnemonic::SKED)
-        | exact("SKEI").map(|_| Mnemonic::SKEI)
-        | exact("SKET").map(|_| Mnemonic::SKET)
-        | exact("SKIP").map(|_| Mnemonic::SKIP)
-        | exact("SUBT").map(|_| Mnemonic::SUBT)
-        | exact("NILX").map(|_| Mnemonic::NILX)
-        | exact("SWAP").map(|_| Mnemonic::NILX)
-        | exact("TAKE").map(|_| Mnemonic::TAKE)
-        | exact("TNEG").map(|_| Mnemonic::TNEG)
-        | exact("TNOT").map(|_| Mnemonic::TNOT)
-        | exact("TOUT").map(|_| Mnemonic::TOUT)
-        | exact("TSTR").map(|_| Mnemonic::TSTR)
-        | exact("TTTT").map(|_| Mnemonic::TTTT)
-        | exact("TTYP").map(|_| Mnemonic::TTYP)
-        | exact("TTYZ").map(|_| Mnemonic::TTYZ)
-        | exact("ADD").map(|_| Mnemonic::ADD)
-        | exact("AND").map(|_| Mnemonic::AND)
-        | exact("DIV").map(|_| Mnemonic::DIV)
-        | exact("DVD").map(|_| Mnemonic::DVD)
-        | exact("JAT").map(|_| Mnemonic::JAT)
-        | exact("JEZ").map(|_| Mnemonic::JEZ)
-        | exact("JGZ").map(|_| Mnemonic::JGZ)
-        | exact("JLZ").map(|_| Mnemonic::JLZ)
-        | exact("JNZ").map(|_| Mnemonic::JNZ)
-        | exact("JZD").map(|_| Mnemonic::JZD)
-        | exact("JZI").map(|_| Mnemonic::JZI)
-        | exact("NIL").map(|_| Mnemonic::NIL)
-        | exact("ORX").map(|_| Mnemonic::ORX)
-        | exact("PIN").map(|_| Mnemonic::PIN)
-        | exact("PUT").map(|_| Mnemonic::PUT)
-        | exact("ROT").map(|_| Mnemonic::ROT)
-        | exact("SHL").map(|_| Mnemonic::SHL)
-        | exact("OR").map(|_| Mnemonic::OR)
-        | exact("SQRT").map(|_| Mnemonic::SQRT)
-        | exact("LN").map(|_| Mnemonic::LN)
-        | exact("EXP").map(|_| Mnemonic::EXP)
-        | exact("READ").map(|_| Mnemonic::READ)
-        | exact("PRINT").map(|_| Mnemonic::PRINT)
-        | exact("SIN").map(|_| Mnemonic::SIN)
-        | exact("COS").map(|_| Mnemonic::COS)
-        | exact("TAN").map(|_| Mnemonic::TAN)
-        | exact("ATN").map(|_| Mnemonic::ATN)
-        | exact("STOP").map(|_| Mnemonic::STOP)
-        | exact("LINE").map(|_| Mnemonic::LINE)
-        | exact("INT").map(|_| Mnemonic::INT)
-        | exact("FRAC").map(|_| Mnemonic::FRAC)
-        | exact("FLOAT").map(|_| Mnemonic::FLOAT)
-        | exact("CAPN").map(|_| Mnemonic::CAPN)
-        | exact("PAGE").map(|_| Mnemonic::PAGE)
-        | exact("RND").map(|_| Mnemonic::RND)
-        | exact("ABS").map(|_| Mnemonic::ABS))
-    .name("mnemonic")
+// Mnemonics are tried in order, so longer spellings precede their prefixes.
+pub(super) const MNEMONICS: &[(&str, Mnemonic)] = &[
+    ("DBYTE", Mnemonic::DBYTE),
+    ("EXTRA", Mnemonic::EXTRA),
+    ("MOCKP", Mnemonic::MOCKP),
+    ("MOCKS", Mnemonic::MOCKS),
+    ("MPLYX", Mnemonic::MULTX),
+    ("MULTX", Mnemonic::MULTX),
+    ("NEQVX", Mnemonic::NEQVX),
+    ("SUBTX", Mnemonic::SUBTX),
+    ("ADDX", Mnemonic::ADDX),
+    ("ANDX", Mnemonic::ANDX),
+    ("DDIV", Mnemonic::DDIV),
+    ("DECR", Mnemonic::DECR),
+    ("DMULT", Mnemonic::DMULT),
+    ("DROT", Mnemonic::DROT),
+    ("DSHL", Mnemonic::DSHL),
+    ("DVDX", Mnemonic::DVDX),
+    ("EXEC", Mnemonic::EXEC),
+    ("INCR", Mnemonic::INCR),
+    ("JUMP", Mnemonic::JUMP),
+    ("MPLY", Mnemonic::MULT),
+    ("MULT", Mnemonic::MULT),
+    ("NEQV", Mnemonic::NEQV),
+    ("NTHG", Mnemonic::NIL),
+    ("PNEG", Mnemonic::PNEG),
+    ("PNOT", Mnemonic::PNOT),
+    ("POWR", Mnemonic::POWR),
+    ("PSQU", Mnemonic::PSQU),
+    ("PTYP", Mnemonic::PTYP),
+    ("PTYZ", Mnemonic::PTYZ),
+    ("PPPP", Mnemonic::PPPP),
+    ("SKAE", Mnemonic::SKAE),
+    ("SKAL", Mnemonic::SKAL),
+    ("SKAG", Mnemonic::SKAG),
+    ("SKAN", Mnemonic::SKAN),
+    ("SKED", Mnemonic::SKED),
+    ("SKEI", Mnemonic::SKEI),
+    ("SKET", Mnemonic::SKET),
+    ("SKIP", Mnemonic::SKIP),
+    ("SUBT", Mnemonic::SUBT),
+    ("NILX", Mnemonic::NILX),
+    ("SWAP", Mnemonic::NILX),
+    ("TAKE", Mnemonic::TAKE),
+    ("TNEG", Mnemonic::TNEG),
+    ("TNOT", Mnemonic::TNOT),
+    ("TOUT", Mnemonic::TOUT),
+    ("TSTR", Mnemonic::TSTR),
+    ("TTTT", Mnemonic::TTTT),
+    ("TTYP", Mnemonic::TTYP),
+    ("TTYZ", Mnemonic::TTYZ),
+    ("ADD", Mnemonic::ADD),
+    ("AND", Mnemonic::AND),
+    ("DIV", Mnemonic::DIV),
+    ("DVD", Mnemonic::DVD),
+    ("JAT", Mnemonic::JAT),
+    ("JEZ", Mnemonic::JEZ),
+    ("JGZ", Mnemonic::JGZ),
+    ("JLZ", Mnemonic::JLZ),
+    ("JNZ", Mnemonic::JNZ),
+    ("JZD", Mnemonic::JZD),
+    ("JZI", Mnemonic::JZI),
+    ("NIL", Mnemonic::NIL),
+    ("ORX", Mnemonic::ORX),
+    ("PIN", Mnemonic::PIN),
+    ("PUT", Mnemonic::PUT),
+    ("ROT", Mnemonic::ROT),
+    ("SHL", Mnemonic::SHL),
+    ("OR", Mnemonic::OR),
+    ("SQRT", Mnemonic::SQRT),
+    ("LN", Mnemonic::LN),
+    ("EXP", Mnemonic::EXP),
+    // READER and PUNCH are not in the original instruction set. Their codes are
+    // numbered on from ABS, the last of the original library routines, so they
+    // are called by EXTRA 19 and EXTRA 20.
+    ("READER", Mnemonic::READER),
+    ("READ", Mnemonic::READ),
+    ("PRINT", Mnemonic::PRINT),
+    ("SIN", Mnemonic::SIN),
+    ("COS", Mnemonic::COS),
+    ("TAN", Mnemonic::TAN),
+    ("ATN", Mnemonic::ATN),
+    ("STOP", Mnemonic::STOP),
+    ("LINE", Mnemonic::LINE),
+    ("INT", Mnemonic::INT),
+    ("FRAC", Mnemonic::FRAC),
+    ("FLOAT", Mnemonic::FLOAT),
+    ("CAPN", Mnemonic::CAPN),
+    ("PAGE", Mnemonic::PAGE),
+    ("RND", Mnemonic::RND),
+    ("ABS", Mnemonic::ABS),
+    // EXTRA 20, numbered on from the original library as READER is.
+    ("PUNCH", Mnemonic::PUNCH),
+];
+
+pub(super) fn mnemonic<'a>() -> Parser<'a, Mnemonic> {
+    MNEMONICS
+        .iter()
+        .map(|(tag, m)| exact(tag).map(move |_| *m))
+        .reduce(|a, b| a | b)
+        .unwrap()
+        .name("mnemonic")
 }
 
 // Utility parsers
@@ -0,0 +1,321 @@
+use super::assembly::Location;
+use super::ast::{Identifier, Mnemonic};
+use super::disassembler::{library_routine, operand, source};
+use super::flow::{Edge, Flow, Target};
+use super::memory::{Instruction, MemoryIndex, Word, WordType};
+use super::object::ObjectImage;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// ****************************************************************************
+// The control-flow graph of a program is written in the DOT language of
+// Graphviz, so that it can be drawn with, for example:
+//
+//     bbc-x cfg sqrt.bbc | dot -Tsvg > sqrt.svg
+//
+// Each node is a basic block: a run of instructions entered only at its first
+// and left only at its last. A block ends at a jump, a skip, EXTRA STOP, EXTRA
+// CAPN and its text, or EXEC. A block is named by the symbols at its first
+// location, or else by the location. Edges are labelled with the condition on
+// which they are taken, such as "A1 = 0", or "else" when it does not hold. A
+// call with JUMP 7 is followed by an edge to the word after it, for the return.
+// Blocks that stop have a double border. Only blocks that can be reached from
+// the entry are drawn.
+
+const RETURN: &str = "return";
+const UNKNOWN: &str = "unknown";
+
+/// The lines of a DOT digraph for the control flow of an image.
+pub fn control_flow_graph(image: &ObjectImage, name: &str, memory_size: usize) -> Vec<String> {
+    let flow = Flow::new(image, memory_size);
+    let mut names = HashMap::<Location, Vec<Identifier>>::new();
+    for (location, identifier) in image.symbols() {
+        names.entry(*location).or_default().push(identifier.clone());
+    }
+    let labels = names
+        .iter()
+        .map(|(location, identifiers)| (*location, identifiers[0].clone()))
+        .collect::<HashMap<_, _>>();
+
+    let blocks = blocks(&flow);
+    let mut lines = vec![
+        format!("digraph \"{}\" {{", escape(name)),
+        "    node [shape=box, fontname=\"Courier\"];".to_string(),
+        "    entry [shape=point];".to_string(),
+        format!("    entry -> {};", node(flow.entry())),
+    ];
+    let mut others = BTreeSet::new();
+    for (first, block) in &blocks {
+        let mut text = vec![block_name(*first, &names)];
+        for location in &block.locations {
+            let word = flow.word(*location).unwrap();
+            let source = source(word, &|instruction| operand(instruction, &labels))
+                .unwrap_or_else(|| format!("{:?}", word.content_type()));
+            text.push(format!("{:04}  {}", location, source));
+        }
+        let stops = block.edges.is_empty();
+        lines.push(format!(
+            "    {} [label=\"{}\\l\"{}];",
+            node(*first),
+            text.iter()
+                .map(|line| escape(line))
+                .collect::<Vec<_>>()
+                .join("\\l"),
+            if stops { ", peripheries=2" } else { "" }
+        ));
+
+        let instruction = flow.instruction(block.last).unwrap();
+        for edge in &block.edges {
+            let to = match edge.target {
+                Target::Location(location) if blocks.contains_key(&location) => node(location),
+                Target::Location(location) => {
+                    others.insert((node(location), data_label(location, flow.word(location))));
+                    node(location)
+                }
+                Target::Return => {
+                    others.insert((RETURN.to_string(), RETURN.to_string()));
+                    RETURN.to_string()
+                }
+                Target::Unknown => {
+                    others.insert((UNKNOWN.to_string(), "?".to_string()));
+                    UNKNOWN.to_string()
+                }
+            };
+            let label = edge_label(&instruction, edge, &labels);
+            let attributes = match (label.is_empty(), is_exec(&instruction) && edge.jump) {
+                (_, true) => format!(" [label=\"{}\", style=dashed]", escape(&label)),
+                (true, _) => String::new(),
+                (false, _) => format!(" [label=\"{}\"]", escape(&label)),
+            };
+            lines.push(format!("    {} -> {}{};", node(*first), to, attributes));
+        }
+    }
+    for (id, label) in others {
+        lines.push(format!(
+            "    {} [label=\"{}\", shape=plaintext];",
+            id,
+            escape(&label)
+        ));
+    }
+    lines.push("}".to_string());
+    lines
+}
+
+// The locations of a block run on past its last instruction to hold CAPN text.
+struct Block {
+    locations: Vec<Location>,
+    last: Location,
+    edges: Vec<Edge>,
+}
+
+// A block starts at the entry, at the target of any transfer that is not to the
+// next word, and after any instruction that transfers elsewhere.
+fn blocks(flow: &Flow) -> BTreeMap<Location, Block> {
+    let reachable = flow.reachable();
+    let mut leaders = BTreeSet::from([flow.entry()]);
+    for location in &reachable {
+        let instruction = flow.instruction(*location).unwrap();
+        let edges = edges(flow, *location, &instruction);
+        if !is_straight(*location, &edges) {
+            for edge in edges {
+                if let Target::Location(target) = edge.target {
+                    leaders.insert(target);
+                }
+            }
+            leaders.insert(location + 1);
+        }
+    }
+
+    let mut blocks = BTreeMap::new();
+    for first in leaders.iter().filter(|first| reachable.contains(first)) {
+        let mut locations = vec![*first];
+        loop {
+            let location = *locations.last().unwrap();
+            let instruction = flow.instruction(location).unwrap();
+            let edges = edges(flow, location, &instruction);
+            let next = location + 1;
+            if is_straight(location, &edges)
+                && reachable.contains(&next)
+                && !leaders.contains(&next)
+            {
+                locations.push(next);
+                continue;
+            }
+            if let Some(Mnemonic::CAPN) = library_routine(&instruction) {
+                locations
+                    .extend((next..).take_while(|l| flow.word(*l).is_some_and(Word::is_sword)));
+            }
+            let block = Block {
+                locations,
+                last: location,
+                edges,
+            };
+            blocks.insert(*first, block);
+            break;
+        }
+    }
+    blocks
+}
+
+// EXEC runs the word at its operand, then goes on to the next word.
+fn edges(flow: &Flow, location: Location, instruction: &Instruction) -> Vec<Edge> {
+    let mut edges = flow.edges(location, instruction);
+    if is_exec(instruction) {
+        edges.push(Edge {
+            target: flow.operand(instruction),
+            jump: true,
+            condition: None,
+        });
+    }
+    edges
+}
+
+fn is_straight(location: Location, edges: &[Edge]) -> bool {
+    matches!(
+        edges,
+        [Edge {
+            target: Target::Location(next),
+            jump: false,
+            condition: None,
+        }] if *next == location + 1
+    )
+}
+
+fn is_exec(instruction: &Instruction) -> bool {
+    instruction.function() == Mnemonic::EXEC
+}
+
+fn edge_label(
+    instruction: &Instruction,
+    edge: &Edge,
+    labels: &HashMap<Location, Identifier>,
+) -> String {
+    let accumulator = format!("A{}", instruction.accumulator().memory_index());
+    let operand = operand(instruction, labels);
+    match (instruction.function(), edge.condition) {
+        (Mnemonic::EXEC, _) if edge.jump => "exec".to_string(),
+        (Mnemonic::JUMP, None) if instruction.accumulator().memory_index() == 7 => {
+            if edge.jump { "call" } else { RETURN }.to_string()
+        }
+        (_, Some(false)) => "else".to_string(),
+        (function, Some(true)) => match function {
+            Mnemonic::JEZ | Mnemonic::JZD | Mnemonic::JZI => format!("{} = 0", accumulator),
+            Mnemonic::JNZ => format!("{} != 0", accumulator),
+            Mnemonic::JAT => format!("{} is a number", accumulator),
+            Mnemonic::JLZ => format!("{} < 0", accumulator),
+            Mnemonic::JGZ => format!("{} > 0", accumulator),
+            Mnemonic::SKAE | Mnemonic::SKED | Mnemonic::SKEI => {
+                format!("{} = {}", accumulator, operand)
+            }
+            Mnemonic::SKAN => format!("{} != {}", accumulator, operand),
+            Mnemonic::SKET => format!("type of {} = type of {}", accumulator, operand),
+            Mnemonic::SKAL => format!("{} < {}", accumulator, operand),
+            Mnemonic::SKAG => format!("{} > {}", accumulator, operand),
+            _ => String::new(),
+        },
+        _ => String::new(),
+    }
+}
+
+fn block_name(location: Location, names: &HashMap<Location, Vec<Identifier>>) -> String {
+    match names.get(&location) {
+        Some(identifiers) => format!("{} ({:04})", identifiers.join(", "), location),
+        None => format!("{:04}", location),
+    }
+}
+
+fn data_label(location: Location, word: Option<&Word>) -> String {
+    let held = match word.map(Word::content_type) {
+        Some(WordType::IWord) => "I-word",
+        Some(WordType::FWord) => "F-word",
+        Some(WordType::SWord) => "S-word",
+        Some(WordType::PWord) => "P-word",
+        _ => "no word",
+    };
+    format!("{:04} {}", location, held)
+}
+
+fn node(location: Location) -> String {
+    format!("L{:04}", location)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bbcx::assembler::Assembler;
+    use crate::bbcx::parser::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn graph_source(input: &str) -> Vec<String> {
+        let ast = input
+            .lines()
+            .map(|line| Parser::parse_line(line).unwrap())
+            .collect::<Vec<_>>();
+        let assembly = Assembler::default().build(&ast).unwrap();
+        let image = ObjectImage::new(assembly, 1024).unwrap();
+        control_flow_graph(&image, "test", 1024)
+    }
+
+    #[test]
+    fn will_split_blocks_at_transfers() {
+        let graph = graph_source(
+            r#"START:  TAKE    1, +3
+LOOP:   SUBT    1, +1
+        JNZ     1, LOOP
+        SKAE    1, +0
+        EXTRA   0, 10
+        STOP"#,
+        );
+        assert_eq!(
+            graph,
+            vec![
+                "digraph \"test\" {",
+                "    node [shape=box, fontname=\"Courier\"];",
+                "    entry [shape=point];",
+                "    entry -> L0008;",
+                "    L0008 [label=\"START (0008)\\l0008  TAKE    1, 1023\\l\"];",
+                "    L0008 -> L0009;",
+                "    L0009 [label=\"LOOP (0009)\\l0009  SUBT    1, 1022\\l0010  JNZ     1, LOOP\\l\"];",
+                "    L0009 -> L0009 [label=\"A1 != 0\"];",
+                "    L0009 -> L0011 [label=\"else\"];",
+                "    L0011 [label=\"0011\\l0011  SKAE    1, 1021\\l\"];",
+                "    L0011 -> L0013 [label=\"A1 = 1021\"];",
+                "    L0011 -> L0012 [label=\"else\"];",
+                "    L0012 [label=\"0012\\l0012  STOP    0\\l\", peripheries=2];",
+                "    L0013 [label=\"0013\\l0013  STOP    0\\l\", peripheries=2];",
+                "}",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_show_calls_returns_and_exec() {
+        let graph = graph_source(
+            r#"        JUMP    7, SUB
+        EXEC    0, DONE
+SUB:    JUMP    0, *6
+DONE:   STOP
+        JUMP    0, 3(1)"#,
+        );
+        assert_eq!(
+            graph[4..],
+            vec![
+                "    L0008 [label=\"0008\\l0008  JUMP    7, SUB\\l\"];",
+                "    L0008 -> L0010 [label=\"call\"];",
+                "    L0008 -> L0009 [label=\"return\"];",
+                "    L0009 [label=\"0009\\l0009  EXEC    0, DONE\\l\"];",
+                "    L0009 -> L0010;",
+                "    L0009 -> L0011 [label=\"exec\", style=dashed];",
+                "    L0010 [label=\"SUB (0010)\\l0010  JUMP    0, *6\\l\"];",
+                "    L0010 -> return;",
+                "    L0011 [label=\"DONE (0011)\\l0011  STOP    0\\l\", peripheries=2];",
+                "    return [label=\"return\", shape=plaintext];",
+                "}",
+            ]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use super::ast::Position;
+use super::macros::Fields;
+
+use crate::result::{Error, Result};
+
+use std::path::{Path, PathBuf};
+
+// ****************************************************************************
+// Files are included on the source text, before macros are expanded.
+//
+//         INCLUDE "name"
+//
+// The file is searched for in the folder of the including file, then in each
+// of the include paths. Names starting "std/" refer to the standard library
+// bundled with the assembler. The INCLUDE line is kept, followed by the lines
+// of the included file, each with its position in that file.
+
+pub const INCLUDE: &str = "INCLUDE";
+const STDLIB_PREFIX: &str = "std/";
+const STDLIB: &[(&str, &str)] = &[("std/numbers.bbc", include_str!("stdlib/numbers.bbc"))];
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct SourceText {
+    text: String,
+    position: Position,
+}
+
+impl SourceText {
+    pub fn new(text: &str, position: Position) -> Self {
+        let text = text.to_string();
+        Self { text, position }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+}
+
+// A file to be included: how it is identified for cycle detection, how it is
+// named in diagnostics, the folder its own includes are relative to, and its text.
+struct Included {
+    key: PathBuf,
+    name: String,
+    folder: Option<PathBuf>,
+    text: String,
+}
+
+#[derive(Default)]
+pub struct SourceReader {
+    include_paths: Vec<PathBuf>,
+    including: Vec<PathBuf>,
+    lines: Vec<SourceText>,
+    errors: Vec<String>,
+}
+
+impl SourceReader {
+    pub fn with_include_paths(mut self, include_paths: Vec<PathBuf>) -> Self {
+        self.include_paths = include_paths;
+        self
+    }
+
+    pub fn read(mut self, path: &Path) -> Result<Vec<SourceText>> {
+        let filename = path.display().to_string();
+        let content = std::fs::read(path)
+            .map_err(|e| Error::CannotReadFile(filename.clone(), e.to_string()))?;
+        let content = String::from_utf8(content)
+            .map_err(|e| Error::CannotReadFile(filename.clone(), e.to_string()))?;
+
+        self.including.push(key(path));
+        self.include(&content, None, path.parent());
+
+        if self.errors.is_empty() {
+            Ok(self.lines)
+        } else {
+            Err(Error::FailedToAssemble(self.errors))
+        }
+    }
+
+    fn include(&mut self, text: &str, file: Option<&str>, folder: Option<&Path>) {
+        for (i, line) in text.lines().enumerate() {
+            let position = file.map_or(Position::new(i + 1), |file| {
+                Position::new(i + 1).with_file(file)
+            });
+            self.lines.push(SourceText::new(line, position.clone()));
+
+            let fields = Fields::from(line);
+            if fields.keyword != INCLUDE {
+                continue;
+            }
+
+            let name = fields
+                .operands
+                .strip_prefix('"')
+                .and_then(|name| name.strip_suffix('"'))
+                .filter(|name| !name.is_empty());
+            let error = match name {
+                _ if !fields.prefix.trim().is_empty() => {
+                    Some("INCLUDE cannot have a location or label".into())
+                }
+                None => Some("INCLUDE expects a quoted file name".into()),
+                Some(name) => match self.resolve(name, folder) {
+                    Err(error) => Some(error),
+                    Ok(included) if self.including.contains(&included.key) => {
+                        Some(format!("INCLUDE of \"{}\" is circular", name))
+                    }
+                    Ok(included) => {
+                        self.including.push(included.key);
+                        self.include(
+                            &included.text,
+                            Some(&included.name),
+                            included.folder.as_deref(),
+                        );
+                        self.including.pop();
+                        None
+                    }
+                },
+            };
+            if let Some(error) = error {
+                self.errors.push(format!("Line {}: {}", position, error));
+            }
+        }
+    }
+
+    fn resolve(&self, name: &str, folder: Option<&Path>) -> std::result::Result<Included, String> {
+        if name.starts_with(STDLIB_PREFIX) {
+            return STDLIB
+                .iter()
+                .find(|(library, _)| *library == name)
+                .map(|(library, text)| Included {
+                    key: PathBuf::from(library),
+                    name: library.to_string(),
+                    folder: None,
+                    text: text.to_string(),
+                })
+                .ok_or(format!("cannot find library file \"{}\"", name));
+        }
+
+        let path = folder
+            .into_iter()
+            .chain(self.include_paths.iter().map(PathBuf::as_path))
+            .map(|folder| folder.join(name))
+            .find(|path| path.is_file())
+            .ok_or(format!("cannot find include file \"{}\"", name))?;
+        let text = std::fs::read_to_string(&path)
+            .map_err(|e| format!("cannot read include file \"{}\": {}", name, e))?;
+        Ok(Included {
+            key: key(&path),
+            name: path.display().to_string(),
+            folder: path.parent().map(Path::to_path_buf),
+            text,
+        })
+    }
+}
+
+fn key(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or(path.to_path_buf())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
+
+    fn write(folder: &Path, name: &str, text: &str) -> PathBuf {
+        let path = folder.join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, text).unwrap();
+        path
+    }
+
+    fn positions(lines: &[SourceText]) -> Vec<(String, &str)> {
+        lines
+            .iter()
+            .map(|line| (line.position().to_string(), line.text()))
+            .collect()
+    }
+
+    #[test]
+    fn will_include_files_from_folder_and_include_paths() {
+        let folder = TempDir::new("bbcx-tests-include").unwrap();
+        let main = write(
+            folder.path(),
+            "main.bbc",
+            "        INCLUDE \"local.bbc\"\n        INCLUDE \"shared.bbc\"\n        STOP",
+        );
+        write(folder.path(), "local.bbc", "LOCAL:  +1");
+        let shared = write(folder.path(), "lib/shared.bbc", "SHARED: +2");
+
+        let lines = SourceReader::default()
+            .with_include_paths(vec![folder.path().join("lib")])
+            .read(&main)
+            .unwrap();
+        let local = folder.path().join("local.bbc").display().to_string();
+        let shared = shared.display().to_string();
+        assert_eq!(
+            positions(&lines),
+            vec![
+                ("1".into(), "        INCLUDE \"local.bbc\""),
+                (format!("1 of {}", local), "LOCAL:  +1"),
+                ("2".into(), "        INCLUDE \"shared.bbc\""),
+                (format!("1 of {}", shared), "SHARED: +2"),
+                ("3".into(), "        STOP"),
+            ]
+        );
+    }
+
+    #[test]
+    fn will_include_standard_library() {
+        let folder = TempDir::new("bbcx-tests-include").unwrap();
+        let main = write(
+            folder.path(),
+            "main.bbc",
+            "        INCLUDE \"std/numbers.bbc\"",
+        );
+
+        let lines = SourceReader::default().read(&main).unwrap();
+        assert_eq!(lines.len(), STDLIB[0].1.lines().count() + 1);
+        assert_eq!(lines[1].position().to_string(), "1 of std/numbers.bbc");
+    }
+
+    #[test]
+    fn will_report_missing_and_circular_includes() {
+        let folder = TempDir::new("bbcx-tests-include").unwrap();
+        let main = write(
+            folder.path(),
+            "main.bbc",
+            "        INCLUDE \"missing.bbc\"\n        INCLUDE \"loop.bbc\"\nX:      INCLUDE \"loop.bbc\"",
+        );
+        let looped = write(folder.path(), "loop.bbc", "        INCLUDE \"main.bbc\"");
+
+        let result = SourceReader::default().read(&main);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Line 1: cannot find include file \"missing.bbc\"".into(),
+                format!(
+                    "Line 1 of {}: INCLUDE of \"main.bbc\" is circular",
+                    looped.display()
+                ),
+                "Line 3: INCLUDE cannot have a location or label".into(),
+            ]))
+        );
+    }
+}
@@ -0,0 +1,242 @@
+use super::assembler::validate_resolved_operands;
+use super::assembly::Assembly;
+use super::ast::Identifier;
+use super::memory::MemoryConfig;
+
+use crate::result::{Error, Result};
+
+// ****************************************************************************
+// Modules are assembled separately and linked into one program.
+//
+//         EXPORT  NAME, ...
+//         IMPORT  NAME, ...
+//
+// Each exported identifier can be used by the modules that import it. Every
+// other identifier is private to its module, and is prefixed by the module name
+// when linked. The first module stays where it was assembled, and each of the
+// others is relocated to follow the one before it. Storage for literals and
+// undefined identifiers is then allocated for the whole program.
+
+#[derive(Debug, PartialEq)]
+pub struct Linker {
+    modules: Vec<(String, Assembly)>,
+    memory_size: usize,
+}
+
+impl Default for Linker {
+    fn default() -> Self {
+        Self {
+            modules: Vec::new(),
+            memory_size: MemoryConfig::default().size(),
+        }
+    }
+}
+
+impl Linker {
+    pub fn with_module(mut self, name: &str, module: Assembly) -> Self {
+        self.modules.push((name.to_string(), module));
+        self
+    }
+
+    pub fn with_memory_size(mut self, memory_size: usize) -> Self {
+        self.memory_size = memory_size;
+        self
+    }
+
+    pub fn link(&self) -> Result<Assembly> {
+        let errors = self.external_errors();
+        if !errors.is_empty() {
+            return Err(Error::FailedToAssemble(errors));
+        }
+
+        let mut errors = Vec::new();
+        let mut next_location = None;
+        let mut program: Option<Assembly> = None;
+        for (name, module) in &self.modules {
+            let warnings = module
+                .warnings()
+                .iter()
+                .map(|warning| format!("Module {}: {}", name, warning))
+                .collect();
+            let mut module = module.clone().qualified(name).with_warnings(warnings);
+            if let Some((first, last)) = module.extent() {
+                let to = next_location.unwrap_or(first);
+                let last = last - first + to;
+                if last >= self.memory_size {
+                    errors.push(format!(
+                        "Module {} placed at {:04}-{:04} does not fit in memory",
+                        name, to, last
+                    ));
+                }
+                module = module.relocated(first, to);
+                next_location = Some(last + 1);
+            }
+            program = Some(match program {
+                Some(program) => program.merged(module),
+                None => module,
+            });
+        }
+        if !errors.is_empty() {
+            return Err(Error::FailedToAssemble(errors));
+        }
+
+        let program = program.ok_or(Error::FailedToAssemble(vec!["No modules to link".into()]))?;
+        let allocated = program.clone().allocate_storage(self.memory_size)?;
+        // Operands can use identifiers from other modules, so are only resolved
+        // once the program has been linked and its storage allocated.
+        validate_resolved_operands(&allocated, self.memory_size, |location| {
+            format!("Location {:04}", location)
+        })?;
+        Ok(program)
+    }
+
+    // Each imported identifier must be exported by exactly one other module.
+    fn external_errors(&self) -> Vec<String> {
+        let mut errors = Vec::new();
+        let mut exported: Vec<(&Identifier, &str)> = Vec::new();
+        for (name, module) in &self.modules {
+            for identifier in module.exports() {
+                match exported.iter().find(|(used, _)| *used == identifier) {
+                    Some((_, first)) => errors.push(format!(
+                        "Duplicate external {} exported by {} and {}",
+                        identifier, first, name
+                    )),
+                    None => exported.push((identifier, name)),
+                }
+            }
+        }
+
+        for (name, module) in &self.modules {
+            module
+                .imports()
+                .iter()
+                .filter(|identifier| !exported.iter().any(|(used, _)| used == identifier))
+                .for_each(|identifier| {
+                    errors.push(format!(
+                        "Unresolved external {} imported by {}",
+                        identifier, name
+                    ))
+                });
+        }
+        errors
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bbcx::assembler::Assembler;
+    use crate::bbcx::ast::SourceWord;
+    use crate::bbcx::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn module(input: &str) -> Assembly {
+        let ast = input
+            .lines()
+            .map(|line| Parser::parse_line(line).unwrap())
+            .collect::<Vec<_>>();
+        Assembler::default().build_module(&ast).unwrap()
+    }
+
+    #[test]
+    fn will_link_modules_into_one_program() {
+        let main = module(
+            r#"        IMPORT  DOUBLE, LIMIT
+START:  TAKE    1, COUNT
+        JUMP    7, DOUBLE
+        PUT     1, COUNT
+        TAKE    2, LIMIT(1)
+        STOP
+COUNT:  +21
+"#,
+        );
+        let library = module(
+            r#"        EXPORT  DOUBLE, LIMIT
+LIMIT:  EQU     100
+COUNT:  +0
+DOUBLE: ADD     1, 1
+        JUMP    *6
+"#,
+        );
+
+        let program = Linker::default()
+            .with_module("main", main)
+            .with_module("lib", library)
+            .link()
+            .unwrap();
+
+        assert_eq!(program.location("main.START"), Some(8));
+        assert_eq!(program.location("main.COUNT"), Some(13));
+        assert_eq!(program.location("lib.COUNT"), Some(14));
+        assert_eq!(program.location("DOUBLE"), Some(15));
+        assert_eq!(program.location("LIMIT"), Some(100));
+        assert_eq!(program.first_pword_location(), Some(8));
+
+        let linked = program.linked_code();
+        let SourceWord::PWord(call) = &linked[&9] else {
+            panic!("Expected a P-word")
+        };
+        assert_eq!(
+            program.address_used_by_store_operand(call.store_operand()),
+            15
+        );
+    }
+
+    #[test]
+    fn will_relocate_labels_below_the_code_of_a_module() {
+        let main = module("        IMPORT  TOP\n        JUMP    0, TOP");
+        let library = module(
+            r#"        EXPORT  TOP
+TOP:
+        ORIGIN  100
+        STOP
+"#,
+        );
+
+        let program = Linker::default()
+            .with_module("main", main)
+            .with_module("lib", library)
+            .link()
+            .unwrap();
+
+        assert_eq!(program.location("TOP"), Some(9));
+        assert_eq!(program.first_pword_location(), Some(8));
+    }
+
+    #[test]
+    fn fails_on_unresolved_and_duplicate_externals() {
+        let main = module("        IMPORT  DOUBLE, HALVE\n        STOP");
+        let first = module("        EXPORT  DOUBLE\nDOUBLE: ADD     1, 1");
+        let second = module("        EXPORT  DOUBLE\nDOUBLE: ADD     1, 1");
+
+        let result = Linker::default()
+            .with_module("main", main)
+            .with_module("first", first)
+            .with_module("second", second)
+            .link();
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Duplicate external DOUBLE exported by first and second".into(),
+                "Unresolved external HALVE imported by main".into(),
+            ]))
+        );
+    }
+
+    #[test]
+    fn fails_when_exported_identifiers_are_not_defined() {
+        let ast = "        EXPORT  DOUBLE\n        IMPORT  HALF\nHALF:   +1"
+            .lines()
+            .map(Parser::parse_line)
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>();
+        let result = Assembler::default().build_module(&ast);
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Line 1: exported identifier DOUBLE is not defined".into(),
+                "Line 2: imported identifier HALF is also defined".into(),
+            ]))
+        );
+    }
+}
@@ -0,0 +1,375 @@
+use super::assembler::{resolve_locations, DEFAULT_ORIGIN};
+use super::assembly::Location;
+use super::ast::{Mnemonic, Position, SourceLine};
+use super::disassembler::library_routine;
+use super::flow::{reads, Flow, Target};
+use super::memory::{Instruction, MemoryIndex, Word, WordType};
+use super::object::ObjectImage;
+
+use std::collections::{BTreeMap, HashMap};
+
+// ****************************************************************************
+// A program is checked for likely mistakes without running it. Each warning
+// has a code that does not change between releases:
+//
+//     L001  unreachable code
+//     L002  a jump to a word of data
+//     L003  control running on from an instruction into data
+//     L004  CAPN not followed by S-words
+//     L005  a double-length instruction on accumulator 0
+//     L006  a read of a location that is never written
+//
+// A warning is suppressed by NOLINT in the comment of its line, or by NOLINT
+// followed by the codes to suppress, such as "; NOLINT L006". In a macro, the
+// comment is on the line of the body.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Check {
+    Unreachable,
+    JumpIntoData,
+    RunIntoData,
+    CapnWithoutText,
+    DoubleLength,
+    NeverWritten,
+}
+
+impl Check {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Check::Unreachable => "L001",
+            Check::JumpIntoData => "L002",
+            Check::RunIntoData => "L003",
+            Check::CapnWithoutText => "L004",
+            Check::DoubleLength => "L005",
+            Check::NeverWritten => "L006",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warning {
+    check: Check,
+    location: Location,
+    position: Option<Position>,
+    message: String,
+}
+
+impl std::fmt::Display for Warning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.position {
+            Some(position) => write!(f, "Line {}: ", position)?,
+            None => write!(f, "Location {:04}: ", self.location)?,
+        }
+        write!(f, "{} {}", self.check.code(), self.message)
+    }
+}
+
+/// The warnings for a program, in location order.
+pub fn lint(ast: &[SourceLine], image: &ObjectImage, memory_size: usize) -> Vec<Warning> {
+    let lines = Lines::new(ast);
+    let flow = Flow::new(image, memory_size);
+    let names = image
+        .symbols()
+        .iter()
+        .map(|(location, name)| (*location, name.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    let mut found = Vec::new();
+    found.extend(unreachable(&flow, &lines));
+    found.extend(runs_into_data(&flow, &names));
+    for (location, instruction) in flow.instructions().filter(|(l, _)| lines.contains(*l)) {
+        let routine = library_routine(&instruction);
+        if routine == Some(Mnemonic::CAPN) && !flow.word(location + 1).is_some_and(Word::is_sword) {
+            found.push((
+                location,
+                Check::CapnWithoutText,
+                "CAPN is not followed by text".into(),
+            ));
+        }
+        let is_double = matches!(
+            instruction.function(),
+            Mnemonic::DSHL | Mnemonic::DROT | Mnemonic::DMULT | Mnemonic::DDIV
+        );
+        if is_double && instruction.accumulator().memory_index() == 0 {
+            found.push((
+                location,
+                Check::DoubleLength,
+                format!(
+                    "{:?} uses accumulators -1 and 0, as accumulator 0 has no pair",
+                    instruction.function()
+                ),
+            ));
+        }
+        if let Some(message) = never_written(&flow, &instruction, &names, memory_size) {
+            found.push((location, Check::NeverWritten, message));
+        }
+    }
+
+    found.sort_by_key(|(location, check, _)| (*location, check.code()));
+    found
+        .into_iter()
+        .filter(|(location, check, _)| !lines.is_suppressed(*location, *check))
+        .map(|(location, check, message)| Warning {
+            check,
+            location,
+            position: lines.position(location),
+            message,
+        })
+        .collect()
+}
+
+// Each run of unreachable instructions is reported once, at its first word.
+// Instructions used as data or pointers by other instructions are not code.
+fn unreachable(flow: &Flow, lines: &Lines) -> Vec<(Location, Check, String)> {
+    let reachable = flow.reachable();
+
+    let mut found = Vec::new();
+    let mut previous = None;
+    for (location, _) in flow.instructions() {
+        let is_unreachable =
+            lines.contains(location) && !reachable.contains(&location) && !flow.is_data(location);
+        if is_unreachable && previous != Some(location.wrapping_sub(1)) {
+            found.push((location, Check::Unreachable, "unreachable code".into()));
+        }
+        previous = is_unreachable.then_some(location);
+    }
+    found
+}
+
+fn runs_into_data(flow: &Flow, names: &HashMap<Location, &str>) -> Vec<(Location, Check, String)> {
+    let mut found = Vec::new();
+    for location in flow.reachable() {
+        let Some(instruction) = flow.instruction(location) else {
+            continue;
+        };
+        for edge in flow.edges(location, &instruction) {
+            let Target::Location(target) = edge.target else {
+                continue;
+            };
+            if flow.is_instruction(target) {
+                continue;
+            }
+            let held = held(flow.word(target));
+            let found_here = if edge.jump {
+                (
+                    Check::JumpIntoData,
+                    format!("jumps to {}, which {}", name(target, names), held),
+                )
+            } else {
+                (
+                    Check::RunIntoData,
+                    format!("runs on to {}, which {}", name(target, names), held),
+                )
+            };
+            if !found.contains(&(location, found_here.0, found_here.1.clone())) {
+                found.push((location, found_here.0, found_here.1));
+            }
+        }
+    }
+    found
+}
+
+fn never_written(
+    flow: &Flow,
+    instruction: &Instruction,
+    names: &HashMap<Location, &str>,
+    memory_size: usize,
+) -> Option<String> {
+    if !reads(instruction.function()) || !flow.has_known_writes() {
+        return None;
+    }
+    let Target::Location(location) = flow.operand(instruction) else {
+        return None;
+    };
+    let is_memory = (DEFAULT_ORIGIN..memory_size).contains(&location);
+    (is_memory && flow.word(location).is_none() && !flow.is_written(location))
+        .then(|| format!("reads {}, which is never written", name(location, names)))
+}
+
+fn held(word: Option<&Word>) -> &'static str {
+    match word.map(Word::content_type) {
+        Some(WordType::IWord) => "holds an I-word",
+        Some(WordType::FWord) => "holds an F-word",
+        Some(WordType::SWord) => "holds an S-word",
+        _ => "holds no word",
+    }
+}
+
+fn name(location: Location, names: &HashMap<Location, &str>) -> String {
+    match names.get(&location) {
+        Some(name) => format!("{:04} ({})", location, name),
+        None => format!("{:04}", location),
+    }
+}
+
+// The source lines of the program words, by location, with their suppressions.
+struct Lines {
+    positions: BTreeMap<Location, Option<Position>>,
+    suppressions: HashMap<Location, Vec<String>>,
+}
+
+const NOLINT: &str = "NOLINT";
+
+impl Lines {
+    fn new(ast: &[SourceLine]) -> Self {
+        let mut positions = BTreeMap::new();
+        let mut suppressions = HashMap::new();
+        for line in resolve_locations(ast) {
+            let Some(first) = line.location() else {
+                continue;
+            };
+            let codes = suppressed_codes(line.comment());
+            for location in first..first + line.size() {
+                positions.insert(location, line.position().cloned());
+                if let Some(codes) = &codes {
+                    suppressions.insert(location, codes.clone());
+                }
+            }
+        }
+        Self {
+            positions,
+            suppressions,
+        }
+    }
+
+    fn contains(&self, location: Location) -> bool {
+        self.positions.contains_key(&location)
+    }
+
+    fn position(&self, location: Location) -> Option<Position> {
+        self.positions.get(&location).cloned().flatten()
+    }
+
+    // No codes after NOLINT suppresses every warning.
+    fn is_suppressed(&self, location: Location, check: Check) -> bool {
+        self.suppressions
+            .get(&location)
+            .is_some_and(|codes| codes.is_empty() || codes.iter().any(|code| code == check.code()))
+    }
+}
+
+fn suppressed_codes(comment: &str) -> Option<Vec<String>> {
+    let mut words = comment
+        .trim_start_matches(';')
+        .split(|c: char| c.is_whitespace() || c == ',');
+    words.find(|word| *word == NOLINT)?;
+    Some(
+        words
+            .take_while(|word| word.is_empty() || is_code(word))
+            .filter(|word| !word.is_empty())
+            .map(String::from)
+            .collect(),
+    )
+}
+
+fn is_code(word: &str) -> bool {
+    word.len() == 4 && word.starts_with('L') && word[1..].chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bbcx::assembler::Assembler;
+    use crate::bbcx::parser::Parser;
+    use pretty_assertions::assert_eq;
+
+    fn lint_source(input: &str) -> Vec<String> {
+        let ast = input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| {
+                Parser::parse_line(line)
+                    .unwrap()
+                    .with_position(Position::new(i + 1))
+            })
+            .collect::<Vec<_>>();
+        let assembly = Assembler::default().build(&ast).unwrap();
+        let image = ObjectImage::new(assembly, 1024).unwrap();
+        lint(&ast, &image, 1024)
+            .iter()
+            .map(Warning::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn will_find_likely_mistakes() {
+        let warnings = lint_source(
+            r#"START:  TAKE    1, COUNT
+        DSHL    0, 4
+        JEZ     1, DATA
+        EXTRA   0, 15
+        PRINT   1
+        JUMP    0, START
+        TAKE    2, 1
+        STOP
+DATA:   +1
+        "TEXT""#,
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "Line 1: L006 reads 1023 (COUNT), which is never written",
+                "Line 2: L005 DSHL uses accumulators -1 and 0, as accumulator 0 has no pair",
+                "Line 3: L002 jumps to 0016 (DATA), which holds an I-word",
+                "Line 4: L004 CAPN is not followed by text",
+                "Line 7: L001 unreachable code",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_find_control_running_into_data() {
+        let warnings = lint_source(
+            r#"        EXTRA   0, 15
+        "HI"
+        SKAE    1, +0
+        PRINT   1
+COUNT:  +0"#,
+        );
+        assert_eq!(
+            warnings,
+            vec![
+                "Line 3: L003 runs on to 0012 (COUNT), which holds an I-word",
+                "Line 4: L003 runs on to 0012 (COUNT), which holds an I-word",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_follow_calls_pointers_and_stores() {
+        let warnings = lint_source(
+            r#"        JUMP    7, SUB
+        JUMP    0, *PTR
+        TAKE    1, TOTAL
+SUB:    PUT     1, TOTAL
+        JUMP    0, *6
+PTR:    NIL     0, DONE
+DONE:   STOP"#,
+        );
+        assert_eq!(warnings, vec!["Line 3: L001 unreachable code"]);
+    }
+
+    #[test]
+    fn will_suppress_warnings() {
+        let warnings = lint_source(
+            r#"        TAKE    1, COUNT      ; NOLINT
+        TAKE    1, TOTAL      ; NOLINT L001, L006
+        DSHL    0, 4          ; NOLINT L006
+        STOP"#,
+        );
+        assert_eq!(
+            warnings,
+            vec!["Line 3: L005 DSHL uses accumulators -1 and 0, as accumulator 0 has no pair"]
+        );
+    }
+
+    #[test]
+    fn will_find_no_warnings_in_historical_programs() {
+        let folder = std::path::Path::new("./examples/sir_fredric_osborn_school_wgc_may_1974");
+        for program in ["alph", "area", "base", "sort", "sqrt"] {
+            let path = folder.join(program).with_extension("bbc");
+            let warnings = lint_source(&std::fs::read_to_string(&path).unwrap());
+            assert_eq!(warnings, Vec::<String>::new(), "{}", program);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use super::assembler::resolve_locations;
+use super::assembly::{Assembly, Location};
+use super::ast::SourceLine;
+use super::memory::Word;
+use super::object::{object_word, ObjectImage};
+
+use std::collections::HashMap;
+
+// ****************************************************************************
+// A listing shows the object code beside the source, as the line-printer
+// listings did. Each line that places words in memory is preceded by the
+// location and object word of the first of them, in the form of an object file:
+//
+//     0016 P 10101777           START:    TAKE    1, COUNT
+//
+// A location given in the source is then left out of the source column.
+// Further words of the line, such as those of a long text, follow on lines of
+// their own. A label on a line that places no words, such as a reserved block,
+// shows just its location. The storage map follows the source, giving the
+// object words allocated by the assembler for identifiers and literals.
+
+/// The width of the object code column, including the space after it.
+pub const OBJECT_WIDTH: usize = 18;
+
+/// The object code column for each assembled line of a program, in order.
+pub fn object_code(ast: &[SourceLine], image: &ObjectImage) -> Vec<Vec<String>> {
+    let words = words(image);
+    resolve_locations(ast)
+        .iter()
+        .map(|line| {
+            let Some(first) = line.location() else {
+                return Vec::new();
+            };
+            let columns = (first..first + line.size())
+                .filter(|location| words.contains_key(location))
+                .map(|location| object_column(location, &words))
+                .collect::<Vec<_>>();
+            if columns.is_empty() && line.label().name().is_some() {
+                vec![object_column(first, &words)]
+            } else {
+                columns
+            }
+        })
+        .collect()
+}
+
+/// The storage map of an assembly whose storage has been allocated, with the
+/// object word of each allocated location.
+pub fn storage_map(assembly: &Assembly, image: &ObjectImage) -> Vec<String> {
+    let words = words(image);
+    assembly
+        .storage_map_entries()
+        .into_iter()
+        .map(|(location, range, description)| {
+            let column = match assembly.storage().contains_key(&location) {
+                true => object_column(location, &words),
+                false => range,
+            };
+            format!("{:<OBJECT_WIDTH$}{}", column, description)
+        })
+        .collect()
+}
+
+fn words(image: &ObjectImage) -> HashMap<Location, Word> {
+    image.words().iter().copied().collect()
+}
+
+// A location without a word, such as a reserved block or an identifier that is
+// only given a value when run, shows just the location.
+fn object_column(location: Location, words: &HashMap<Location, Word>) -> String {
+    match words.get(&location) {
+        Some(word) => format!("{:04} {}", location, object_word(word)),
+        None => format!("{:04}", location),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bbcx::assembler::Assembler;
+    use crate::bbcx::parser::Parser;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn will_show_object_code_and_storage_map() {
+        let ast = r#"        ORIGIN  16
+START:  TAKE    1, COUNT
+        ADD     1, +1
+        PRINT   1
+        TEXT    "HELLO"
+        STOP"#
+            .lines()
+            .map(|line| Parser::parse_line(line).unwrap())
+            .collect::<Vec<_>>();
+        let assembly = Assembler::default()
+            .build(&ast)
+            .and_then(|assembly| assembly.allocate_storage(1024))
+            .unwrap();
+        let image = ObjectImage::new(assembly.clone(), 1024).unwrap();
+        assert_eq!(
+            object_code(&ast, &image),
+            vec![
+                vec![],
+                vec!["0016 P 10101777".to_string()],
+                vec!["0017 P 04101776".to_string()],
+                vec!["0018 P 77100005".to_string()],
+                vec!["0019 S 10051414".to_string(), "0020 S 17757575".to_string()],
+                vec!["0021 P 77000012".to_string()],
+            ]
+        );
+        assert_eq!(
+            storage_map(&assembly, &image),
+            vec![
+                "0016-0021         program",
+                "1022 I 00000001   literal +1 used at 0017",
+                "1023              identifier COUNT",
+            ]
+        );
+    }
+}
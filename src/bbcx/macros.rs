@@ -0,0 +1,460 @@
+use super::ast::{Location, Position};
+use super::grammar::{label, location, DIRECTIVES, MNEMONICS};
+use super::include::{SourceText, INCLUDE};
+
+use crate::result::{Error, Result};
+
+use std::collections::HashMap;
+
+// ****************************************************************************
+// Macros are expanded on the source text, before it is parsed.
+//
+//         MACRO   NAME PARAM1, PARAM2
+//         ...
+//         ENDM
+//
+// A definition must precede its use. A macro is invoked with its name in the
+// place of a mnemonic, followed by one argument for each parameter. Every
+// parameter in the body is replaced by the argument text. Labels defined in
+// the body are local to each expansion and are renamed with a unique suffix.
+// Any location or label on the invocation line is kept on a line of its own,
+// ahead of the expansion.
+
+const MACRO: &str = "MACRO";
+const END_MACRO: &str = "ENDM";
+const MAX_DEPTH: usize = 16;
+
+/// How a line of the expanded program relates to the source.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Origin {
+    Source,
+    Definition,
+    Invocation,
+    Expansion,
+    Include,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ExpandedLine {
+    text: String,
+    position: Position,
+    origin: Origin,
+}
+
+impl ExpandedLine {
+    fn new(text: &str, position: &Position, origin: Origin) -> Self {
+        let text = text.to_string();
+        let position = position.clone();
+        Self {
+            text,
+            position,
+            origin,
+        }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The position of the source line, or of the invocation for an expansion.
+    pub fn position(&self) -> &Position {
+        &self.position
+    }
+
+    pub fn origin(&self) -> Origin {
+        self.origin
+    }
+
+    pub fn is_assembled(&self) -> bool {
+        matches!(self.origin, Origin::Source | Origin::Expansion)
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Macro {
+    parameters: Vec<String>,
+    body: Vec<String>,
+}
+
+#[derive(Default)]
+pub struct MacroProcessor {
+    macros: HashMap<String, Macro>,
+    expansions: usize,
+    lines: Vec<ExpandedLine>,
+    errors: Vec<String>,
+}
+
+impl MacroProcessor {
+    pub fn expand(mut self, lines: &[SourceText]) -> Result<Vec<ExpandedLine>> {
+        let mut i = 0;
+        while i < lines.len() {
+            let (text, position) = (lines[i].text(), lines[i].position());
+            let fields = Fields::from(text);
+            match fields.keyword {
+                MACRO => {
+                    let end = lines[i + 1..]
+                        .iter()
+                        .position(|line| Fields::from(line.text()).keyword == END_MACRO)
+                        .map(|end| i + 1 + end);
+                    let last = end.unwrap_or(lines.len() - 1);
+                    match end {
+                        Some(end) => self.define(&fields, &lines[i + 1..end], position),
+                        None => self.error(position, "MACRO without ENDM".into()),
+                    }
+                    for line in &lines[i..=last] {
+                        self.push(line.text(), line.position(), Origin::Definition);
+                    }
+                    i = last;
+                }
+                END_MACRO => {
+                    self.error(position, "ENDM without MACRO".into());
+                    self.push(text, position, Origin::Definition);
+                }
+                INCLUDE => self.push(text, position, Origin::Include),
+                name if self.macros.contains_key(name) => {
+                    self.push(text, position, Origin::Invocation);
+                    self.invoke(&fields, position, 0);
+                }
+                _ => self.push(text, position, Origin::Source),
+            }
+            i += 1;
+        }
+
+        if self.errors.is_empty() {
+            Ok(self.lines)
+        } else {
+            Err(Error::FailedToAssemble(self.errors))
+        }
+    }
+
+    fn define(&mut self, fields: &Fields, body: &[SourceText], position: &Position) {
+        let mut operands = fields.operands.splitn(2, char::is_whitespace);
+        let name = operands.next().unwrap_or_default().to_string();
+        let parameters = split_operands(operands.next().unwrap_or_default());
+
+        let is_reserved = MNEMONICS.iter().any(|(mnemonic, _)| *mnemonic == name)
+            || DIRECTIVES.contains(&name.as_str())
+            || [MACRO, END_MACRO, INCLUDE].contains(&name.as_str());
+
+        let error = if !is_identifier(&name) {
+            Some(format!("invalid macro name \"{}\"", name))
+        } else if is_reserved {
+            Some(format!("macro name {} is reserved", name))
+        } else if self.macros.contains_key(&name) {
+            Some(format!("macro {} is already defined", name))
+        } else if let Some(parameter) = parameters.iter().find(|p| !is_identifier(p)) {
+            Some(format!("invalid macro parameter \"{}\"", parameter))
+        } else if body
+            .iter()
+            .any(|line| Fields::from(line.text()).keyword == MACRO)
+        {
+            Some(format!("macro {} contains a definition", name))
+        } else {
+            None
+        };
+
+        match error {
+            Some(error) => self.error(position, error),
+            None => {
+                let body = body.iter().map(|line| line.text().to_string()).collect();
+                self.macros.insert(name, Macro { parameters, body });
+            }
+        }
+    }
+
+    fn invoke(&mut self, fields: &Fields, position: &Position, depth: usize) {
+        let name = fields.keyword;
+        if depth == MAX_DEPTH {
+            self.error(position, format!("macro {} nests too deeply", name));
+            return;
+        }
+
+        let Macro { parameters, body } = self.macros[name].clone();
+        let arguments = split_operands(fields.operands);
+        if arguments.len() != parameters.len() {
+            let error = format!(
+                "macro {} expects {} arguments, found {}",
+                name,
+                parameters.len(),
+                arguments.len()
+            );
+            self.error(position, error);
+            return;
+        }
+
+        self.expansions += 1;
+        let suffix = format!("Q{}", self.expansions);
+        let substitutions = parameters
+            .into_iter()
+            .zip(arguments)
+            .chain(body.iter().filter_map(|line| {
+                Fields::from(line.as_str())
+                    .label
+                    .map(|label| (label.clone(), format!("{}{}", label, suffix)))
+            }))
+            .collect::<HashMap<_, _>>();
+
+        if !fields.prefix.trim().is_empty() {
+            self.push(fields.prefix, position, Origin::Expansion);
+        }
+        for line in body {
+            let text = substitute(&line, &substitutions);
+            let fields = Fields::from(text.as_str());
+            if fields.keyword == INCLUDE {
+                self.push(&text, position, Origin::Include);
+            } else if self.macros.contains_key(fields.keyword) {
+                self.push(&text, position, Origin::Invocation);
+                self.invoke(&fields, position, depth + 1);
+            } else {
+                self.push(&text, position, Origin::Expansion);
+            }
+        }
+    }
+
+    fn push(&mut self, text: &str, position: &Position, origin: Origin) {
+        self.lines.push(ExpandedLine::new(text, position, origin));
+    }
+
+    fn error(&mut self, position: &Position, error: String) {
+        self.errors.push(format!("Line {}: {}", position, error));
+    }
+}
+
+// A source line split into its optional location and label, the word in the
+// place of a mnemonic, the remaining operands and any comment.
+#[derive(Debug, PartialEq)]
+pub(super) struct Fields<'a> {
+    pub(super) prefix: &'a str,
+    pub(super) location: Option<Location>,
+    pub(super) label: Option<String>,
+    pub(super) keyword: &'a str,
+    pub(super) operands: &'a str,
+    pub(super) comment: &'a str,
+}
+
+impl<'a> From<&'a str> for Fields<'a> {
+    fn from(line: &'a str) -> Self {
+        let input = line.as_bytes();
+        let start = line.len() - line.trim_start().len();
+        let (location, position) = location()
+            .parse_at(input, start)
+            .map_or((None, start), |(location, position)| {
+                (Some(location), position)
+            });
+        let (label, position) = label()
+            .parse_at(input, position)
+            .map_or((None, position), |(label, position)| {
+                (label.name(), position)
+            });
+        let position = position + line[position..].len() - line[position..].trim_start().len();
+
+        let keyword_end = line[position..]
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .map_or(line.len(), |end| position + end);
+        let rest = &line[keyword_end..];
+        let operands = strip_comment(rest);
+
+        Self {
+            prefix: &line[..position],
+            location,
+            label,
+            keyword: &line[position..keyword_end],
+            operands: operands.trim(),
+            comment: rest[operands.len()..].trim(),
+        }
+    }
+}
+
+fn strip_comment(text: &str) -> &str {
+    let mut quoted = false;
+    for (i, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => return &text[..i],
+            _ => {}
+        }
+    }
+    text
+}
+
+// Operands are separated by commas outside of quotes.
+fn split_operands(text: &str) -> Vec<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return Vec::new();
+    }
+
+    let mut operands = vec![String::new()];
+    let mut quoted = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                operands.last_mut().unwrap().push(c);
+            }
+            ',' if !quoted => operands.push(String::new()),
+            _ => operands.last_mut().unwrap().push(c),
+        }
+    }
+    operands
+        .into_iter()
+        .map(|operand| operand.trim().to_string())
+        .collect()
+}
+
+fn is_identifier(text: &str) -> bool {
+    let mut chars = text.chars();
+    chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit())
+}
+
+// Replace whole identifiers outside of quotes and comments.
+fn substitute(line: &str, substitutions: &HashMap<String, String>) -> String {
+    let mut result = String::new();
+    let mut token = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    let flush = |token: &mut String, result: &mut String| {
+        let replacement = substitutions
+            .get(token.as_str())
+            .filter(|_| is_identifier(token));
+        result.push_str(replacement.unwrap_or(token));
+        token.clear();
+    };
+
+    while let Some(c) = chars.next() {
+        if !quoted && c.is_ascii_alphanumeric() {
+            token.push(c);
+            continue;
+        }
+        flush(&mut token, &mut result);
+        match c {
+            '"' => quoted = !quoted,
+            ';' if !quoted => {
+                result.push(c);
+                result.extend(chars.by_ref());
+                break;
+            }
+            _ => {}
+        }
+        result.push(c);
+    }
+    flush(&mut token, &mut result);
+    result
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn expand(input: &str) -> Result<Vec<ExpandedLine>> {
+        let lines = input
+            .lines()
+            .enumerate()
+            .map(|(i, line)| SourceText::new(line, Position::new(i + 1)))
+            .collect::<Vec<_>>();
+        MacroProcessor::default().expand(&lines)
+    }
+
+    fn assembled(lines: &[ExpandedLine]) -> Vec<(&str, Position)> {
+        lines
+            .iter()
+            .filter(|line| line.is_assembled())
+            .map(|line| (line.text(), line.position().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn will_expand_macro_with_parameters() {
+        let program = r#"        MACRO   PRNT MSG
+        EXTRA   15          ; CAPN MSG
+        TEXT    MSG
+        EXTRA   11          ; LINE
+        ENDM
+START:  PRNT    "HELLO, WORLD"
+        STOP"#;
+        let lines = expand(program).unwrap();
+        assert_eq!(
+            assembled(&lines),
+            vec![
+                ("START:  ", Position::new(6)),
+                ("        EXTRA   15          ; CAPN MSG", Position::new(6)),
+                ("        TEXT    \"HELLO, WORLD\"", Position::new(6)),
+                ("        EXTRA   11          ; LINE", Position::new(6)),
+                ("        STOP", Position::new(7)),
+            ]
+        );
+        assert_eq!(lines[0].origin(), Origin::Definition);
+        assert_eq!(lines[5].origin(), Origin::Invocation);
+    }
+
+    #[test]
+    fn will_rename_local_labels_in_each_expansion() {
+        let program = r#"        MACRO   WAIT N
+        TAKE    1, N
+LOOP:   SUBT    1, +1
+        JUMP    1, LOOP(4)
+        ENDM
+        WAIT    10
+        WAIT    COUNT"#;
+        let lines = expand(program).unwrap();
+        assert_eq!(
+            assembled(&lines),
+            vec![
+                ("        TAKE    1, 10", Position::new(6)),
+                ("LOOPQ1:   SUBT    1, +1", Position::new(6)),
+                ("        JUMP    1, LOOPQ1(4)", Position::new(6)),
+                ("        TAKE    1, COUNT", Position::new(7)),
+                ("LOOPQ2:   SUBT    1, +1", Position::new(7)),
+                ("        JUMP    1, LOOPQ2(4)", Position::new(7)),
+            ]
+        );
+    }
+
+    #[test]
+    fn will_expand_nested_invocations() {
+        let program = r#"        MACRO   NEWLN
+        EXTRA   11
+        ENDM
+        MACRO   TWOLN
+        NEWLN
+        NEWLN
+        ENDM
+        TWOLN"#;
+        let lines = expand(program).unwrap();
+        assert_eq!(
+            assembled(&lines),
+            vec![
+                ("        EXTRA   11", Position::new(8)),
+                ("        EXTRA   11", Position::new(8))
+            ]
+        );
+    }
+
+    #[test]
+    fn will_report_macro_errors() {
+        let program = r#"        MACRO   TAKE X
+        ENDM
+        MACRO   TWICE A, B
+        ENDM
+        TWICE   1
+        ENDM
+        MACRO   LOOP
+        LOOP
+        ENDM
+        LOOP
+        MACRO   OPEN"#;
+        assert_eq!(
+            expand(program),
+            Err(Error::FailedToAssemble(vec![
+                "Line 1: macro name TAKE is reserved".into(),
+                "Line 5: macro TWICE expects 2 arguments, found 1".into(),
+                "Line 6: ENDM without MACRO".into(),
+                "Line 10: macro LOOP nests too deeply".into(),
+                "Line 11: MACRO without ENDM".into(),
+            ]))
+        );
+    }
+}
@@ -10,11 +10,12 @@ use num_enum::TryFromPrimitive;
 pub fn word_to_instruction(word: &Word) -> Result<Instruction> {
     let function = Function::try_from_primitive(word.pword_function_bits() as u32)
         .map_err(|err| Error::CannotConvertFromWord(err.to_string()))?;
+    let page = Page::try_from(word.pword_page_bits() as usize)?;
     let instruction = Builder::new(function)
         .with_accumulator(word.pword_accumulator_bits() as usize)
         .with_index_register(word.pword_index_register_bits() as usize)
         .with_indirect(word.pword_indirect_bits() != 0)
-        .with_page(word.pword_page_bits() as usize)
+        .with_page(page)
         .with_address(word.pword_address_bits() as usize);
     Ok(instruction.build())
 }
@@ -17,6 +17,9 @@ pub enum Error {
     #[error("invalid address {0}")]
     InvalidAddress(usize),
 
+    #[error("invalid memory size {0}")]
+    InvalidMemorySize(usize),
+
     #[error("invalid page {0}")]
     InvalidPage(usize),
 
@@ -6,16 +6,120 @@ use super::word::*;
 use crate::bbcx::ast::{Location as AstLocation, Mnemonic, SourceWord as AstSourceWord};
 use crate::bbcx::Assembly;
 
+/// The content given to memory locations that are not loaded by the program.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Fill {
+    /// Unloaded locations remain undefined and fault when used as an operand.
+    #[default]
+    Undefined,
+    /// Unloaded locations are set to the IWord zero.
+    Zero,
+    /// Unloaded locations are set to a conspicuous IWord so reads of uninitialised
+    /// storage stand out in results and memory dumps.
+    Poison,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Config {
+    size: usize,
+    fill: Fill,
+}
+
+impl Config {
+    /// The smallest memory that still holds the accumulators.
+    pub const MIN_SIZE: usize = 8;
+
+    pub fn new(size: usize, fill: Fill) -> Result<Self> {
+        (Config::MIN_SIZE..=MEMORY_SIZE)
+            .contains(&size)
+            .then_some(Self { size, fill })
+            .ok_or(Error::InvalidMemorySize(size))
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn fill(&self) -> Fill {
+        self.fill
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            size: MEMORY_SIZE,
+            fill: Fill::default(),
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub struct State(Vec<Word>);
 
 impl State {
+    const POISON: u64 = 0o5252_5252;
+
+    fn new(config: &Config) -> Self {
+        let mut words = vec![Word::new(WordType::IWord, 0)];
+        words.extend(vec![Word::default(); config.size() - 1]);
+        Self(words)
+    }
+
+    pub fn load(assembly: Assembly, config: &Config) -> Result<Self> {
+        let linked_code = assembly.linked_code();
+        let mut keys = Vec::from_iter(linked_code.keys());
+        keys.sort();
+        let state = keys
+            .into_iter()
+            .try_fold(State::new(config), |acc, location| {
+                let content = &linked_code[location];
+                acc.add_source_word(*location, content, &assembly)
+            })?;
+        Ok(state.fill_unused(config.fill()))
+    }
+
+    /// Memory holding the given words, as read from an object image.
+    pub fn load_words(words: &[(AstLocation, Word)], config: &Config) -> Result<Self> {
+        let state = words
+            .iter()
+            .try_fold(State::new(config), |mut state, &(location, word)| {
+                if location >= state.size() {
+                    return Err(Error::InvalidAddress(location));
+                }
+                state[location] = word;
+                Ok(state)
+            })?;
+        Ok(state.fill_unused(config.fill()))
+    }
+
+    fn fill_unused(mut self, fill: Fill) -> Self {
+        let word = match fill {
+            Fill::Undefined => return self,
+            Fill::Zero => Word::new(WordType::IWord, 0),
+            Fill::Poison => Word::new(WordType::IWord, State::POISON),
+        };
+        self.0
+            .iter_mut()
+            .filter(|w| w.is_undefined())
+            .for_each(|w| *w = word);
+        self
+    }
+
+    pub fn size(&self) -> usize {
+        self.0.len()
+    }
+
     fn add_source_word(
         mut self,
         location: AstLocation,
         source_word: &AstSourceWord,
         assembly: &Assembly,
     ) -> Result<Self> {
+        if location >= self.size() {
+            return Err(Error::InvalidAddress(location));
+        }
+
         match source_word {
             AstSourceWord::IWord(i) => {
                 self[location] = (*i).try_into()?;
@@ -26,14 +130,17 @@ impl State {
             AstSourceWord::PWord(pword) => {
                 let operand = pword.store_operand();
                 let address = if operand.requires_storage() {
-                    let address = self.next_storage_address()?;
+                    let address: Address = assembly
+                        .literal_location(location)
+                        .ok_or(Error::OutOfMemory)?
+                        .try_into()?;
                     self[address] = store_operand_to_word(&operand)?;
                     address
                 } else {
                     assembly.address_used_by_store_operand(operand).try_into()?
                 };
 
-                let instruction = if pword.mnemonic() as usize <= Mnemonic::EXTRA as usize {
+                let instruction = if !pword.mnemonic().is_library_routine() {
                     Builder::new(pword.mnemonic())
                         .with_accumulator(pword.accumulator().as_usize())
                         .with_index_register(pword.index_register())
@@ -78,15 +185,6 @@ impl State {
         Ok(self)
     }
 
-    fn next_storage_address(&self) -> Result<Address> {
-        self.0
-            .iter()
-            .rposition(Word::is_undefined)
-            .and_then(|i| i.try_into().ok())
-            .ok_or(Error::OutOfMemory)
-    }
-
-    #[cfg(test)]
     pub fn iter(&self) -> std::slice::Iter<'_, Word> {
         self.0.iter()
     }
@@ -96,9 +194,7 @@ pub const MEMORY_SIZE: usize = 1024;
 
 impl Default for State {
     fn default() -> Self {
-        let mut words = vec![Word::new(WordType::IWord, 0)];
-        words.extend(vec![Word::default(); MEMORY_SIZE - 1]);
-        Self(words)
+        Self::new(&Config::default())
     }
 }
 
@@ -106,14 +202,7 @@ impl TryFrom<Assembly> for State {
     type Error = Error;
 
     fn try_from(value: Assembly) -> Result<Self> {
-        let linked_code = value.linked_code();
-        let mut keys = Vec::from_iter(linked_code.keys());
-        keys.sort();
-        keys.into_iter()
-            .try_fold(State::default(), |acc, location| {
-                let content = &linked_code[location];
-                acc.add_source_word(*location, content, &value)
-            })
+        State::load(value, &Config::default())
     }
 }
 
@@ -61,6 +61,14 @@ impl Word {
         }
     }
 
+    pub(crate) fn content_type(&self) -> WordType {
+        self.word_type
+    }
+
+    pub(crate) fn raw_bits(&self) -> RawBits {
+        self.raw_bits
+    }
+
     pub fn is_undefined(&self) -> bool {
         self.word_type == WordType::Undefined
     }
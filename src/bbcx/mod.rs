@@ -2,95 +2,442 @@ mod assembler;
 mod assembly;
 mod ast;
 mod charset;
+mod cross_reference;
+mod devices;
+mod diagnostic;
+mod disassembler;
 mod executor;
+mod flow;
+mod formatter;
 mod grammar;
+mod graph;
+mod include;
+mod linker;
+mod lint;
+mod listing;
+mod macros;
 mod memory;
+mod object;
 mod parser;
+mod reconstruct;
 mod result;
 
-use self::assembler::Assembler;
-use self::assembly::Assembly;
+use self::assembler::{Assembler, UndefinedIdentifiers};
+use self::assembly::{Assembly, LiteralPool};
 use self::ast::SourceLine;
+use self::devices::{
+    Channel, Console, Device, DeviceTable, LinePrinter, PaperTapePunch, PaperTapeReader, TapeFrame,
+    Teletype,
+};
 use self::executor::Executor;
+use self::include::SourceReader;
+use self::linker::Linker;
+use self::listing::OBJECT_WIDTH;
+use self::macros::{ExpandedLine, MacroProcessor, Origin};
+use self::memory::{MemoryConfig, MemoryFill};
+use self::object::{ObjectImage, EXTENSION as OBJECT_EXTENSION};
 use self::parser::Parser;
 
-use crate::args::Args;
+use crate::args::{
+    Args, InputDevice, LiteralPool as LiteralPoolArg, MemoryFill as MemoryFillArg, OutputDevice,
+    TapeFrame as TapeFrameArg, UndefinedIdentifiers as UndefinedIdentifiersArg,
+};
 use crate::list_writer::ListWriter;
 use crate::model::*;
 use crate::result::{Error, Result};
+use crate::source_writer::write_formatted;
 
-use std::path::Path;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
 
 pub struct BbcX {
     args: Args,
+    warned: RefCell<HashSet<(String, String)>>,
 }
 
 impl BbcX {
     pub fn new(args: &Args) -> BbcX {
         let args = args.clone();
-        Self { args }
+        Self {
+            args,
+            warned: RefCell::new(HashSet::new()),
+        }
     }
 
-    fn impl_parse(&self, path: &Path) -> Result<Vec<Result<SourceLine>>> {
-        let lines = file_lines(path)?;
-        let results = lines.iter().map(|line| Parser::parse_line(line));
-        Ok(results.collect())
+    fn impl_expand(&self, path: &Path) -> Result<Vec<ExpandedLine>> {
+        let lines = SourceReader::default()
+            .with_include_paths(self.args.include_paths())
+            .read(path)?;
+        MacroProcessor::default().expand(&lines)
+    }
+
+    // Only lines from the source and from macro expansions are parsed.
+    fn impl_parse(&self, lines: &[ExpandedLine]) -> Vec<Option<Result<SourceLine>>> {
+        lines
+            .iter()
+            .map(|line| {
+                line.is_assembled().then(|| {
+                    Parser::parse_line(line.text())
+                        .map(|source_line| source_line.with_position(line.position().clone()))
+                })
+            })
+            .collect()
     }
 
     fn impl_assemble(&self, path: &Path) -> Result<Assembly> {
-        let parsed_lines = self.impl_parse(path)?;
+        let ast = self.impl_ast(path)?;
+        self.impl_build(&ast, path)
+    }
 
-        let parsed_lines_len = parsed_lines.len();
-        let ast = parsed_lines
-            .iter()
-            .filter_map(|l| l.as_ref().ok())
-            .cloned()
-            .collect::<Vec<_>>();
+    fn impl_build(&self, ast: &[SourceLine], path: &Path) -> Result<Assembly> {
+        let assembly = self.assembler().build(ast)?;
+        self.warn(&assembly, &path.display().to_string());
+        Ok(assembly)
+    }
+
+    // Each file is assembled as a module, named by its file stem, then linked.
+    fn impl_link(&self, paths: &[PathBuf]) -> Result<Assembly> {
+        let mut linker = Linker::default().with_memory_size(self.args.memory_size());
+        let mut errors = Vec::new();
+        for path in paths {
+            let module = self
+                .impl_ast(path)
+                .and_then(|ast| self.assembler().build_module(&ast));
+            match module {
+                Ok(module) => linker = linker.with_module(&module_name(path), module),
+                Err(error) => errors.push(error),
+            }
+        }
+        if errors.is_empty() {
+            let assembly = linker.link()?;
+            let names = paths
+                .iter()
+                .map(|path| path.display().to_string())
+                .collect::<Vec<_>>();
+            self.warn(&assembly, &names.join(", "));
+            Ok(assembly)
+        } else {
+            Err(Error::BuildErrors(errors))
+        }
+    }
+
+    fn impl_ast(&self, path: &Path) -> Result<Vec<SourceLine>> {
+        let lines = self.impl_expand(path)?;
+        let parsed_lines = self.impl_parse(&lines);
 
-        let all_ok = parsed_lines_len == ast.len();
+        let all_ok = parsed_lines.iter().flatten().all(|l| l.is_ok());
 
         if all_ok {
-            Assembler::assemble(&ast)
+            let ast = parsed_lines
+                .into_iter()
+                .flatten()
+                .filter_map(Result::ok)
+                .collect::<Vec<_>>();
+            Ok(ast)
         } else {
-            let lines = file_lines(path)?;
             let all_results = parsed_lines
                 .iter()
                 .zip(lines.iter())
-                .map(|(r, l)| match (r, l) {
-                    (Ok(_), l) => format!("        {}", l),
-                    (Err(Error::FailedToParse(e)), l) => format!(" *****  {}\n         {}", l, e),
-                    _ => unreachable!(),
+                .map(|(r, l)| match r {
+                    Some(Err(Error::FailedToParse(e))) => format!(" *****  {}", indent(e)),
+                    _ => format!("{}{}", margin(l), l.text()),
                 })
                 .collect::<Vec<_>>();
             Err(Error::FailedToAssemble(all_results))
         }
     }
 
+    fn assembler(&self) -> Assembler {
+        let undefined_identifiers = match self.args.undefined_identifiers() {
+            UndefinedIdentifiersArg::Allocate => UndefinedIdentifiers::Allocate,
+            UndefinedIdentifiersArg::Warn => UndefinedIdentifiers::Warn,
+            UndefinedIdentifiersArg::Strict => UndefinedIdentifiers::Strict,
+        };
+        let literal_pool = match self.args.literal_pool() {
+            LiteralPoolArg::Shared => LiteralPool::Shared,
+            LiteralPoolArg::PerUse => LiteralPool::PerUse,
+        };
+        Assembler::default()
+            .with_undefined_identifiers(undefined_identifiers)
+            .with_literal_pool(literal_pool)
+            .with_memory_size(self.args.memory_size())
+    }
+
+    // Object files are loaded as they are, without being assembled.
     fn impl_run(&self, path: &Path, trace: Option<&Path>) -> Result<()> {
-        let assembly = self.impl_assemble(path)?;
-        let executor = Executor::new(trace);
+        let image = if ObjectImage::is_object_file(path) {
+            ObjectImage::read(path)?
+        } else {
+            let assembly = self.impl_assemble(path)?;
+            ObjectImage::new(assembly, self.args.memory_size())?
+        };
+        self.impl_execute(&image, trace)
+    }
+
+    fn impl_execute(&self, image: &ObjectImage, trace: Option<&Path>) -> Result<()> {
+        let executor = self
+            .executor(trace)?
+            .with_memory_config(self.memory_config()?);
         _ = executor
-            .execute(&assembly)
+            .execute_image(image)
             .map_err(|err| Error::FailedToRun(err.to_string()))?;
         Ok(())
     }
 
+    fn impl_write_object(&self, assembly: Assembly, path: &Path) -> Result<()> {
+        let parent = path.parent().unwrap().to_path_buf();
+        let parent = self.args.object_path().unwrap_or(parent);
+        let stem = path.file_stem().unwrap();
+        let object_file = parent.join(stem).with_extension(OBJECT_EXTENSION);
+        ObjectImage::new(assembly, self.args.memory_size())?.write(&object_file)
+    }
+
+    fn executor<'a>(&self, trace: Option<&'a Path>) -> Result<Executor<'a>> {
+        let reader = self.tape_reader()?;
+        let punch = self.tape_punch()?;
+
+        let input: Rc<RefCell<dyn Device>> = match (self.args.input(), &reader) {
+            (InputDevice::PaperTape, Some(reader)) => reader.clone(),
+            _ => Rc::new(RefCell::new(Console::default())),
+        };
+
+        let output: Rc<RefCell<dyn Device>> = match (self.args.output(), &punch) {
+            (OutputDevice::Teletype, _) => {
+                let teletype = Teletype::new(
+                    io::stdout(),
+                    self.args.carriage_width(),
+                    self.args.throttle(),
+                );
+                Rc::new(RefCell::new(teletype))
+            }
+            (OutputDevice::LinePrinter, _) => {
+                let line_printer = LinePrinter::new(
+                    io::stdout(),
+                    self.args.page_length(),
+                    self.args.page_width(),
+                )
+                .with_title(self.args.page_title())
+                .with_page_numbers(self.args.page_numbers());
+                Rc::new(RefCell::new(line_printer))
+            }
+            (OutputDevice::PaperTape, Some(punch)) => punch.clone(),
+            _ => Rc::new(RefCell::new(Console::default())),
+        };
+
+        let mut devices = DeviceTable::default()
+            .with_device(Channel::Input, input)
+            .with_device(Channel::Output, output);
+        if let Some(reader) = reader {
+            devices = devices.with_device(Channel::Reader, reader);
+        }
+        if let Some(punch) = punch {
+            devices = devices.with_device(Channel::Punch, punch);
+        }
+
+        Ok(Executor::new(trace).with_devices(devices))
+    }
+
+    // The paper tape reader, attached whenever a tape is given, so that a
+    // program can select it with READER.
+    fn tape_reader(&self) -> Result<Option<Rc<RefCell<dyn Device>>>> {
+        let Some(path) = self.args.tape_reader() else {
+            return Ok(None);
+        };
+        let file = File::open(&path)
+            .map_err(|e| Error::CannotReadFile(path.display().to_string(), e.to_string()))?;
+        let reader = PaperTapeReader::new(BufReader::new(file), self.tape_frame());
+        Ok(Some(Rc::new(RefCell::new(reader))))
+    }
+
+    // The paper tape punch, attached whenever a tape is given, so that a
+    // program can select it with PUNCH.
+    fn tape_punch(&self) -> Result<Option<Rc<RefCell<dyn Device>>>> {
+        let Some(path) = self.args.tape_punch() else {
+            return Ok(None);
+        };
+        let file = File::create(&path)
+            .map_err(|e| Error::CannotToWriteFile(path.display().to_string(), e.to_string()))?;
+        let punch = PaperTapePunch::new(BufWriter::new(file), self.tape_frame());
+        Ok(Some(Rc::new(RefCell::new(punch))))
+    }
+
+    fn tape_frame(&self) -> TapeFrame {
+        match self.args.tape_frame() {
+            TapeFrameArg::Five => TapeFrame::Five,
+            TapeFrameArg::Six => TapeFrame::Six,
+        }
+    }
+
+    fn memory_config(&self) -> Result<MemoryConfig> {
+        let fill = match self.args.memory_fill() {
+            MemoryFillArg::Undefined => MemoryFill::Undefined,
+            MemoryFillArg::Zero => MemoryFill::Zero,
+            MemoryFillArg::Poison => MemoryFill::Poison,
+        };
+        MemoryConfig::new(self.args.memory_size(), fill)
+            .map_err(|err| Error::FailedToRun(err.to_string()))
+    }
+
     fn impl_list(&self, path: &Path) -> Result<()> {
+        if !self.args.list() {
+            return Ok(());
+        }
         let mut writer = ListWriter::new(path, &self.args);
-        let lines = file_lines(path)?;
-        let results = lines.iter().map(|line| Parser::parse_line(line));
-        for line in results {
-            let line = match line {
-                Ok(line) => format!("        {}", line),
-                Err(Error::FailedToParse(error)) => format!(" *****  {}", error),
-                _ => unreachable!(),
-            };
-            writer.add_lines_to_listing(&line);
+        let assembled = self.impl_ast(path).and_then(|ast| {
+            let assembly = self
+                .assembler()
+                .build(&ast)?
+                .allocate_storage(self.args.memory_size())?;
+            let image = ObjectImage::new(assembly.clone(), self.args.memory_size())?;
+            Ok((ast, assembly, image))
+        });
+        let mut object_code = match &assembled {
+            Ok((ast, _, image)) => listing::object_code(ast, image).into_iter(),
+            Err(_) => Vec::new().into_iter(),
+        };
+        let no_object = "";
+        match self.impl_expand(path) {
+            Ok(lines) => {
+                let parsed_lines = self.impl_parse(&lines);
+                for (parsed_line, line) in parsed_lines.into_iter().zip(lines.iter()) {
+                    if let Some(Err(Error::FailedToParse(error))) = &parsed_line {
+                        writer.add_error_to_listing(error_line(line), error);
+                        continue;
+                    }
+                    let text = match parsed_line {
+                        Some(Ok(source_line)) => {
+                            let words = object_code.next().unwrap_or_default();
+                            // The object code column already shows the location.
+                            let source_line = match words.is_empty() {
+                                true => source_line,
+                                false => source_line.without_location(),
+                            };
+                            let mut words = words.iter();
+                            let first = words.next().map_or(no_object, String::as_str);
+                            let mut text =
+                                format!("{}{:<OBJECT_WIDTH$}{}", margin(line), first, source_line);
+                            for word in words {
+                                text.push_str(&format!("\n{}{}", margin(line), word));
+                            }
+                            text
+                        }
+                        None => format!(
+                            "{}{:<OBJECT_WIDTH$}{}",
+                            margin(line),
+                            no_object,
+                            line.text()
+                        ),
+                        _ => unreachable!(),
+                    };
+                    let (first, rest) = match text.split_once('\n') {
+                        Some((first, rest)) => (first, Some(rest)),
+                        None => (text.as_str(), None),
+                    };
+                    match source_line_number(line) {
+                        Some(number) => writer.add_source_to_listing(number, first),
+                        None => writer.add_lines_to_listing(first),
+                    }
+                    if let Some(rest) = rest {
+                        writer.add_lines_to_listing(rest);
+                    }
+                }
+            }
+            Err(Error::FailedToAssemble(errors)) => {
+                for (i, line) in file_lines(path)?.iter().enumerate() {
+                    writer.add_source_to_listing(
+                        i + 1,
+                        &format!("        {:<OBJECT_WIDTH$}{}", no_object, line),
+                    );
+                }
+                for error in errors {
+                    writer.add_error_to_listing(None, &error);
+                }
+            }
+            Err(error) => return Err(error),
+        }
+        if let Ok((ast, assembly, image)) = &assembled {
+            let sections = [
+                ("SYMBOLS", cross_reference::symbol_table(ast, assembly)),
+                ("STORAGE MAP", listing::storage_map(assembly, image)),
+            ];
+            for (section, lines) in sections {
+                if lines.is_empty() {
+                    continue;
+                }
+                writer.start_section(section);
+                for line in lines {
+                    writer.add_lines_to_listing(&format!("        {}", line));
+                }
+            }
         }
         writer
             .write_content_to_file()
             .map_err(|e| Error::CannotToWriteFile(path.display().to_string(), e.to_string()))
     }
+
+    // Warnings are given on every build, but only once for each program, as it
+    // can be built again to be run.
+    fn warn(&self, assembly: &Assembly, name: &str) {
+        for warning in assembly.warnings() {
+            let is_new = self
+                .warned
+                .borrow_mut()
+                .insert((name.to_string(), warning.clone()));
+            if is_new {
+                eprintln!("Warning: {}", warning);
+            }
+        }
+    }
+
+    fn report(&self, assembly: Assembly, name: &str) -> Result<()> {
+        if self.args.storage_map() {
+            let assembly = assembly.allocate_storage(self.args.memory_size())?;
+            println!("Storage map: {}", name);
+            for line in assembly.storage_map() {
+                println!("    {}", line);
+            }
+        }
+        Ok(())
+    }
+}
+
+// Lines from macro expansions and included files are marked in the margin.
+fn margin(line: &ExpandedLine) -> &'static str {
+    match line.origin() {
+        Origin::Expansion => "     +  ",
+        _ if line.position().is_included() => "     I  ",
+        _ => "        ",
+    }
+}
+
+// Only the lines of the program itself are numbered in a listing, by their line
+// in its file.
+fn source_line_number(line: &ExpandedLine) -> Option<usize> {
+    match line.origin() {
+        Origin::Expansion => None,
+        _ if line.position().is_included() => None,
+        _ => Some(line.position().line()),
+    }
+}
+
+// An error in a macro expansion is on the line that invokes the macro.
+fn error_line(line: &ExpandedLine) -> Option<usize> {
+    (!line.position().is_included()).then(|| line.position().line())
+}
+
+// Continuation lines of a diagnostic are aligned with the source line.
+fn indent(diagnostic: &str) -> String {
+    diagnostic.replace('\n', "\n        ")
+}
+
+fn module_name(path: &Path) -> String {
+    path.file_stem().map_or(path.display().to_string(), |stem| {
+        stem.to_string_lossy().to_string()
+    })
 }
 
 fn file_lines(path: &Path) -> Result<Vec<String>> {
@@ -107,16 +454,93 @@ fn file_lines(path: &Path) -> Result<Vec<String>> {
 
 impl LanguageModel for BbcX {
     fn assemble(&self, path: &Path) -> Result<()> {
-        _ = self.impl_assemble(path)?;
-        Ok(())
+        if ObjectImage::is_object_file(path) {
+            return ObjectImage::read(path).map(|_| ());
+        }
+        let assembly = self.impl_assemble(path)?;
+        if self.args.object() {
+            self.impl_write_object(assembly.clone(), path)?;
+        }
+        self.report(assembly, &path.display().to_string())
     }
 
     fn run(&self, path: &Path, trace: Option<&Path>) -> Result<()> {
         self.impl_run(path, trace)
     }
 
+    fn link(&self, paths: &[PathBuf]) -> Result<()> {
+        let assembly = self.impl_link(paths)?;
+        if self.args.object() {
+            self.impl_write_object(assembly.clone(), &paths[0])?;
+        }
+        let names = paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        self.report(assembly, &names.join(", "))
+    }
+
+    fn run_linked(&self, paths: &[PathBuf], trace: Option<&Path>) -> Result<()> {
+        let assembly = self.impl_link(paths)?;
+        let image = ObjectImage::new(assembly, self.args.memory_size())?;
+        self.impl_execute(&image, trace)
+    }
+
+    fn disassemble(&self, path: &Path) -> Result<()> {
+        let image = ObjectImage::read_image(path)?;
+        for line in disassembler::disassemble(&image)? {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+
+    fn reconstruct(&self, path: &Path) -> Result<()> {
+        let ast = self.impl_ast(path)?;
+        for line in reconstruct::reconstruct(&ast)? {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+
+    fn format(&self, path: &Path, check: bool) -> Result<()> {
+        let lines = file_lines(path)?;
+        let formatted = formatter::format(&self.impl_expand(path)?)?;
+        write_formatted(path, &lines, &formatted, check)
+    }
+
+    fn lint(&self, path: &Path) -> Result<()> {
+        let ast = self.impl_ast(path)?;
+        let assembly = self.impl_build(&ast, path)?;
+        let image = ObjectImage::new(assembly, self.args.memory_size())?;
+        let warnings = lint::lint(&ast, &image, self.args.memory_size())
+            .iter()
+            .map(|warning| format!("{}: {}", path.display(), warning))
+            .collect::<Vec<_>>();
+        if warnings.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::LintWarnings(warnings))
+        }
+    }
+
+    fn graph(&self, path: &Path) -> Result<()> {
+        let image = if ObjectImage::is_object_file(path) {
+            ObjectImage::read_image(path)?
+        } else {
+            let assembly = self.impl_assemble(path)?;
+            ObjectImage::new(assembly, self.args.memory_size())?
+        };
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        for line in graph::control_flow_graph(&image, &name, self.args.memory_size()) {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+
     fn list(&self, path: &Path) -> Result<()> {
-        _ = self.impl_list(path);
+        if !ObjectImage::is_object_file(path) {
+            _ = self.impl_list(path);
+        }
         Ok(())
     }
 }
@@ -176,6 +600,103 @@ mod test {
         assert!(list_target.exists());
     }
 
+    #[test]
+    fn will_list_location_once() {
+        let temp_folder = TempDir::new("bbcx-tests-bbcx").unwrap();
+
+        let temp_target = temp_folder.path().join("origin.bbc");
+        let temp_target_str = temp_target.display().to_string();
+        std::fs::write(&temp_target, "0016    TAKE    1, 2\n        STOP\n").unwrap();
+
+        let args = vec!["bbc-x", "--lang=bbc-x", "--list", &temp_target_str]
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+        let args = Args::from(args);
+        let model = BbcX::new(&args);
+        assert!(model.list(&args.files().next().unwrap()).is_ok());
+
+        let listing = std::fs::read_to_string(temp_folder.path().join("origin.lst")).unwrap();
+        let lines = listing.lines().map(str::trim_end).collect::<Vec<_>>();
+        assert_eq!(
+            lines[3..5],
+            [
+                "    1         0016 P 10100002                     TAKE    1, 2",
+                "    2         0017 P 77000012                     STOP",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_report_errors_in_macro_expansions_at_invocation() {
+        let temp_folder = TempDir::new("bbcx-tests-bbcx").unwrap();
+
+        let temp_target = temp_folder.path().join("macro.bbc");
+        let temp_target_str = temp_target.display().to_string();
+
+        let program = r#"        MACRO   FETCH ADDR
+        TAKE    1, ADDR
+        ENDM
+        FETCH   10
+        FETCH   2000
+"#;
+        std::fs::write(&temp_target, program).unwrap();
+
+        let args = vec!["bbc-x", "--lang=bbc-x", &temp_target_str]
+            .into_iter()
+            .map(|s| s.to_string())
+            .collect();
+        let args = Args::from(args);
+        let model = BbcX::new(&args);
+        let result = model.impl_assemble(&args.files().next().unwrap());
+        assert_eq!(
+            result,
+            Err(Error::FailedToAssemble(vec![
+                "Line 5: address 2000 out of range".into()
+            ]))
+        );
+    }
+
+    #[test]
+    fn will_link_and_run_modules() {
+        let temp_folder = TempDir::new("bbcx-tests-bbcx").unwrap();
+
+        let main = temp_folder.path().join("main.bbc");
+        let lib = temp_folder.path().join("lib.bbc");
+        std::fs::write(
+            &main,
+            "        IMPORT  DOUBLE\n        TAKE    1, +2\n        JUMP    7, DOUBLE\n        STOP\n",
+        )
+        .unwrap();
+        std::fs::write(
+            &lib,
+            "        EXPORT  DOUBLE\nDOUBLE: ADD     1, 1\n        JUMP    *6\n",
+        )
+        .unwrap();
+
+        let args = vec![
+            "bbc-x",
+            "--link",
+            "--run",
+            &main.display().to_string(),
+            &lib.display().to_string(),
+        ]
+        .into_iter()
+        .map(|s| s.to_string())
+        .collect();
+        let args = Args::from(args);
+        let model = BbcX::new(&args);
+        let files = args.files().collect::<Vec<_>>();
+        assert!(model.link(&files).is_ok());
+        assert!(model.run_linked(&files, None).is_ok());
+        assert_eq!(
+            model.link(&files[..1]),
+            Err(Error::FailedToAssemble(vec![
+                "Unresolved external DOUBLE imported by main".into()
+            ]))
+        );
+    }
+
     #[test]
     fn will_trace() {
         let temp_folder = TempDir::new("bbcx-tests-bbcx").unwrap();
@@ -0,0 +1,351 @@
+use super::assembler::DEFAULT_ORIGIN;
+use super::assembly::{Assembly, Location};
+use super::ast::Identifier;
+use super::memory::{word_to_instruction, Memory, MemoryConfig, MemoryFill, Word, WordType};
+
+use crate::result::{Error, Result};
+
+use std::path::Path;
+use std::str::FromStr;
+
+// ****************************************************************************
+// An object file holds an assembled memory image, so that a program can be run
+// many times without its source. It is text, with one record on each line:
+//
+//     BBC-X OBJECT 1              the header, giving the version of the format
+//     ENTRY 0008                  the location of the first instruction
+//     SYMBOL 0008 START           the value of a label or allocated identifier
+//     WORD 0008 P 02110017        a word: its location, type and bits in octal
+//
+// Locations and values are decimal. Each word has a type, I for an IWord, F for
+// an FWord, S for an SWord and P for a PWord, followed by its 24 bits as eight
+// octal digits. Symbols are only for reference; they are not needed to run the
+// program. Blank lines and lines starting with ';' are ignored. Locations that
+// have no word are not loaded, and are treated as by '--memory-fill'.
+
+pub const EXTENSION: &str = "obj";
+const HEADER: &str = "BBC-X OBJECT 1";
+
+// A trace ends with a dump of the defined words in memory, one on each line.
+const MEMORY_DUMP: &str = "Memory";
+const TRACE_MARKER: &str = "  -      ";
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ObjectImage {
+    entry: Location,
+    symbols: Vec<(Location, Identifier)>,
+    words: Vec<(Location, Word)>,
+}
+
+impl ObjectImage {
+    /// The memory image of an assembled program, once its storage is allocated.
+    pub fn new(assembly: Assembly, memory_size: usize) -> Result<Self> {
+        let assembly = assembly.allocate_storage(memory_size)?;
+        let config = MemoryConfig::new(memory_size, MemoryFill::Undefined)
+            .map_err(|err| Error::FailedToAssemble(vec![err.to_string()]))?;
+        let memory = Memory::load(assembly.clone(), &config)
+            .map_err(|err| Error::FailedToAssemble(vec![err.to_string()]))?;
+
+        let mut symbols = assembly
+            .symbols()
+            .iter()
+            .map(|(identifier, location)| (*location, identifier.clone()))
+            .collect::<Vec<_>>();
+        symbols.sort();
+        let words = memory
+            .iter()
+            .enumerate()
+            .filter(|(_, word)| !word.is_undefined())
+            .map(|(location, word)| (location, *word))
+            .collect();
+
+        Ok(Self {
+            entry: assembly.first_pword_location().unwrap_or(0),
+            symbols,
+            words,
+        })
+    }
+
+    pub fn entry(&self) -> Location {
+        self.entry
+    }
+
+    pub fn words(&self) -> &[(Location, Word)] {
+        &self.words
+    }
+
+    pub fn symbols(&self) -> &[(Location, Identifier)] {
+        &self.symbols
+    }
+
+    /// The image of the memory dumped at the end of a trace. The accumulators
+    /// hold what was left in them by the run, so are not part of the image. The
+    /// entry point is taken to be the first instruction.
+    pub fn from_memory_dump(text: &str) -> std::result::Result<Self, String> {
+        let mut words = None;
+        for line in text.lines() {
+            if line.trim() == MEMORY_DUMP {
+                words = Some(Vec::new());
+                continue;
+            }
+            let Some((_, record)) = line.split_once(TRACE_MARKER) else {
+                continue;
+            };
+            let fields = record.split_whitespace().collect::<Vec<_>>();
+            let word = match fields[..] {
+                [location, word_type, bits, ..] => {
+                    let word_type = match word_type {
+                        "IWord" => Some(WordType::IWord),
+                        "FWord" => Some(WordType::FWord),
+                        "SWord" => Some(WordType::SWord),
+                        "PWord" => Some(WordType::PWord),
+                        _ => None,
+                    };
+                    let bits = bits
+                        .strip_prefix("0o")
+                        .and_then(|bits| u64::from_str_radix(bits, 8).ok());
+                    location.parse::<Location>().ok().zip(word_type.zip(bits))
+                }
+                _ => None,
+            };
+            match (word, words.as_mut()) {
+                (Some((location, _)), Some(_)) if location < DEFAULT_ORIGIN => {}
+                (Some((location, (word_type, bits))), Some(words)) => {
+                    words.push((location, Word::new(word_type, bits)))
+                }
+                // A later run is traced after the dump.
+                (None, Some(_)) if !record.trim().is_empty() => words = None,
+                _ => {}
+            }
+        }
+
+        let words: Vec<(Location, Word)> = words.ok_or("no memory dump in trace")?;
+        let entry = words
+            .iter()
+            .find(|(_, word)| word.is_instruction())
+            .map_or(0, |(location, _)| *location);
+        Ok(Self {
+            entry,
+            symbols: Vec::new(),
+            words,
+        })
+    }
+
+    /// An object file, or a trace ending in a memory dump.
+    pub fn read_image(path: &Path) -> Result<Self> {
+        if ObjectImage::is_object_file(path) {
+            return ObjectImage::read(path);
+        }
+        let filename = path.display().to_string();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::CannotReadFile(filename.clone(), e.to_string()))?;
+        ObjectImage::from_memory_dump(&text).map_err(|error| Error::CannotReadFile(filename, error))
+    }
+
+    pub fn is_object_file(path: &Path) -> bool {
+        path.extension()
+            .is_some_and(|extension| extension == EXTENSION)
+    }
+
+    pub fn read(path: &Path) -> Result<Self> {
+        let filename = path.display().to_string();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::CannotReadFile(filename.clone(), e.to_string()))?;
+        text.parse()
+            .map_err(|error| Error::CannotReadFile(filename, error))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        std::fs::write(path, self.to_string())
+            .map_err(|e| Error::CannotToWriteFile(path.display().to_string(), e.to_string()))
+    }
+}
+
+impl std::fmt::Display for ObjectImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+        writeln!(f, "ENTRY {:04}", self.entry)?;
+        for (location, identifier) in &self.symbols {
+            writeln!(f, "SYMBOL {:04} {}", location, identifier)?;
+        }
+        for (location, word) in &self.words {
+            writeln!(f, "WORD {:04} {}", location, object_word(word))?;
+        }
+        Ok(())
+    }
+}
+
+/// A word as it is written in an object file: its type, then its bits in octal.
+pub(super) fn object_word(word: &Word) -> String {
+    let word_type = match word.content_type() {
+        WordType::IWord => 'I',
+        WordType::FWord => 'F',
+        WordType::SWord => 'S',
+        WordType::PWord => 'P',
+        WordType::Undefined => unreachable!("Undefined words are not in the image"),
+    };
+    format!("{} {:08o}", word_type, word.raw_bits())
+}
+
+impl FromStr for ObjectImage {
+    type Err = String;
+
+    fn from_str(text: &str) -> std::result::Result<Self, Self::Err> {
+        let mut records = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with(';'));
+
+        match records.next() {
+            Some((_, HEADER)) => {}
+            Some((line, _)) => return Err(format!("line {}: expected \"{}\"", line, HEADER)),
+            None => return Err("empty object file".into()),
+        }
+
+        let mut entry = None;
+        let mut symbols = Vec::new();
+        let mut words: Vec<(Location, Word)> = Vec::new();
+        for (line, record) in records {
+            let error = |error: &str| format!("line {}: {}", line, error);
+            let fields = record.split_whitespace().collect::<Vec<_>>();
+            match fields[..] {
+                ["ENTRY", location] if entry.is_none() => {
+                    entry = Some(parse_location(location).map_err(error)?);
+                }
+                ["ENTRY", _] => return Err(error("more than one entry point")),
+                ["SYMBOL", location, identifier] => {
+                    let location = parse_location(location).map_err(error)?;
+                    symbols.push((location, identifier.to_string()));
+                }
+                ["WORD", location, word_type, bits] => {
+                    let location = parse_location(location).map_err(error)?;
+                    if words.iter().any(|(used, _)| *used == location) {
+                        return Err(error(&format!(
+                            "location {} has more than one word",
+                            location
+                        )));
+                    }
+                    let word_type = match word_type {
+                        "I" => WordType::IWord,
+                        "F" => WordType::FWord,
+                        "S" => WordType::SWord,
+                        "P" => WordType::PWord,
+                        _ => return Err(error(&format!("invalid word type \"{}\"", word_type))),
+                    };
+                    let word = u64::from_str_radix(bits, 8)
+                        .ok()
+                        .filter(|_| bits.len() == 8)
+                        .map(|bits| Word::new(word_type, bits))
+                        .ok_or(error(&format!("invalid word \"{}\"", bits)))?;
+                    if word_type == WordType::PWord {
+                        word_to_instruction(&word).map_err(|err| {
+                            error(&format!("invalid P-word \"{}\": {}", bits, err))
+                        })?;
+                    }
+                    words.push((location, word));
+                }
+                _ => return Err(error(&format!("invalid record \"{}\"", record))),
+            }
+        }
+
+        let entry = entry.ok_or("no entry point")?;
+        Ok(Self {
+            entry,
+            symbols,
+            words,
+        })
+    }
+}
+
+fn parse_location(text: &str) -> std::result::Result<Location, &'static str> {
+    text.parse().map_err(|_| "invalid location")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::bbcx::{assembler::Assembler, executor::Executor, Parser};
+    use pretty_assertions::assert_eq;
+
+    fn assemble(input: &str) -> Assembly {
+        let ast = input
+            .lines()
+            .map(|line| Parser::parse_line(line).unwrap())
+            .collect::<Vec<_>>();
+        Assembler::default().build(&ast).unwrap()
+    }
+
+    #[test]
+    fn will_write_object_image() {
+        let assembly = assemble("DATA:   +5\n        -1");
+        let image = ObjectImage::new(assembly, 16).unwrap();
+        assert_eq!(
+            image.to_string(),
+            r#"BBC-X OBJECT 1
+ENTRY 0000
+SYMBOL 0008 DATA
+WORD 0000 I 00000000
+WORD 0008 I 00000005
+WORD 0009 I 77777777
+"#
+        );
+    }
+
+    #[test]
+    fn will_read_object_image_and_run_it() {
+        let assembly = assemble(
+            r#"START:  TAKE    1, +20
+        ADD     1, VALUE
+        PUT     1, VALUE
+        STOP
+VALUE:  +22"#,
+        );
+        let image = ObjectImage::new(assembly.clone(), 1024).unwrap();
+        let read = image.to_string().parse::<ObjectImage>().unwrap();
+        assert_eq!(read, image);
+        assert_eq!(read.entry(), 8);
+
+        let from_source = Executor::new(None).execute(&assembly).unwrap();
+        let from_image = Executor::new(None).execute_image(&read).unwrap();
+        assert_eq!(from_image, from_source);
+        assert_eq!(from_image[12].as_i64().unwrap(), 42);
+    }
+
+    #[test]
+    fn fails_to_read_invalid_object_image() {
+        let cases = [
+            ("", "empty object file"),
+            ("ENTRY 0008", "line 1: expected \"BBC-X OBJECT 1\""),
+            (
+                "BBC-X OBJECT 1\nWORD 0008 I 5",
+                "line 2: invalid word \"5\"",
+            ),
+            (
+                "BBC-X OBJECT 1\nWORD 0008 X 00000005",
+                "line 2: invalid word type \"X\"",
+            ),
+            (
+                "BBC-X OBJECT 1\nWORD 0008 P 77777777",
+                "line 2: invalid P-word \"77777777\": invalid page 1",
+            ),
+            (
+                "BBC-X OBJECT 1\n; comment\nWORD 0008 I 00000005\nWORD 0008 I 00000006",
+                "line 4: location 8 has more than one word",
+            ),
+            ("BBC-X OBJECT 1\nWORD 0008 I 00000005", "no entry point"),
+            (
+                "BBC-X OBJECT 1\nLOAD 0008",
+                "line 2: invalid record \"LOAD 0008\"",
+            ),
+        ];
+        for (text, error) in cases {
+            assert_eq!(
+                text.parse::<ObjectImage>(),
+                Err(error.to_string()),
+                "{}",
+                text
+            );
+        }
+    }
+}
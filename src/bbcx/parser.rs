@@ -1,4 +1,5 @@
 use super::ast::*;
+use super::diagnostic::Diagnostic;
 use super::grammar::*;
 
 use crate::result::{Error, Result};
@@ -9,11 +10,12 @@ impl Parser {
     pub fn parse_line(input: &str) -> Result<SourceLine> {
         Grammar::bbcx_line()
             .parse(input.trim().as_bytes())
-            .map_err(|_| Error::FailedToParse(input.into()))
+            .map_err(|_| Error::FailedToParse(Diagnostic::new(input).to_string()))
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::approx_constant)]
 mod test {
     use super::*;
     use pretty_assertions::assert_eq;
@@ -83,6 +85,253 @@ mod test {
         assert_eq!(actual[1..], expected);
     }
 
+    #[test]
+    fn free_form_lines() {
+        let program = r#"
+; Comment only
+        ORIGIN 100
+START:  TAKE    1, 20
+
+0200    STOP
+"#;
+        let actual = parse(program);
+        let expected = vec![
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from(None),
+                None,
+                "; Comment only".into(),
+            )),
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from(None),
+                Some(Statement::Directive(Directive::Origin(100))),
+                "".into(),
+            )),
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from("START".to_string()),
+                Some(Statement::SourceWord(SourceWord::PWord(PWord::new(
+                    Mnemonic::TAKE,
+                    '1'.into(),
+                    StoreOperand::AddressOperand(AddressOperand::new(
+                        SimpleAddressOperand::DirectAddress(Address::NumericAddress(20)),
+                        None,
+                    )),
+                )))),
+                "".into(),
+            )),
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from(None),
+                None,
+                "".into(),
+            )),
+            Ok(SourceLine::new(
+                200,
+                Label::from(None),
+                Some(SourceWord::PWord(PWord::new(
+                    Mnemonic::STOP,
+                    None.into(),
+                    StoreOperand::None,
+                ))),
+                "".into(),
+            )),
+        ];
+        assert_eq!(actual[1..], expected);
+    }
+
+    #[test]
+    fn directives() {
+        let program = r#"
+SIZE:   EQU 10
+BUF:    RESERVE 10
+        TEXT "HELLO, WORLD"
+        EXPORT SIZE, BUF
+        IMPORT PDEC
+"#;
+        let actual = parse(program);
+        let expected = vec![
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from("SIZE".to_string()),
+                Some(Statement::Directive(Directive::Equ(10))),
+                "".into(),
+            )),
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from("BUF".to_string()),
+                Some(Statement::Directive(Directive::Reserve(10))),
+                "".into(),
+            )),
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from(None),
+                Some(Statement::Directive(Directive::Text("HELLO, WORLD".into()))),
+                "".into(),
+            )),
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from(None),
+                Some(Statement::Directive(Directive::Export(vec![
+                    "SIZE".into(),
+                    "BUF".into(),
+                ]))),
+                "".into(),
+            )),
+            Ok(SourceLine::from_statement(
+                None,
+                Label::from(None),
+                Some(Statement::Directive(Directive::Import(vec!["PDEC".into()]))),
+                "".into(),
+            )),
+        ];
+        assert_eq!(actual[1..], expected);
+    }
+
+    #[test]
+    fn address_expressions() {
+        let program = r#"
+0001    TAKE    1, TABLE+2*3
+0002    ADD     2, *PTR+1(2)
+0003    SUBT    END-START/2
+"#;
+        let actual = parse(program);
+        let expression = |left: Address, operator: Operator, right: Address| {
+            Address::Expression(Box::new(left), operator, Box::new(right))
+        };
+        let pwords = vec![
+            PWord::new(
+                Mnemonic::TAKE,
+                '1'.into(),
+                StoreOperand::AddressOperand(AddressOperand::new(
+                    SimpleAddressOperand::DirectAddress(expression(
+                        Address::Identifier("TABLE".into()),
+                        Operator::Add,
+                        expression(
+                            Address::NumericAddress(2),
+                            Operator::Multiply,
+                            Address::NumericAddress(3),
+                        ),
+                    )),
+                    None,
+                )),
+            ),
+            PWord::new(
+                Mnemonic::ADD,
+                '2'.into(),
+                StoreOperand::AddressOperand(AddressOperand::new(
+                    SimpleAddressOperand::IndirectAddress(expression(
+                        Address::Identifier("PTR".into()),
+                        Operator::Add,
+                        Address::NumericAddress(1),
+                    )),
+                    Some(2),
+                )),
+            ),
+            PWord::new(
+                Mnemonic::SUBT,
+                None.into(),
+                StoreOperand::AddressOperand(AddressOperand::new(
+                    SimpleAddressOperand::DirectAddress(expression(
+                        Address::Identifier("END".into()),
+                        Operator::Subtract,
+                        expression(
+                            Address::Identifier("START".into()),
+                            Operator::Divide,
+                            Address::NumericAddress(2),
+                        ),
+                    )),
+                    None,
+                )),
+            ),
+        ];
+        let expected = pwords
+            .into_iter()
+            .enumerate()
+            .map(|(i, pword)| {
+                Ok(SourceLine::new(
+                    i + 1,
+                    Label::from(None),
+                    Some(SourceWord::PWord(pword)),
+                    "".into(),
+                ))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(actual[1..], expected);
+        assert!(actual[2]
+            .as_ref()
+            .unwrap()
+            .to_string()
+            .contains("*PTR+1(2)"));
+    }
+
+    #[test]
+    fn parenthesised_address_expressions() {
+        let program = r#"
+0001    TAKE    1, (TABLE+2)*3(4)
+0002    SUBT    END-(START-2)
+0003    ADD     1, (A*B)
+"#;
+        let actual = parse(program);
+        let expression = |left: Address, operator: Operator, right: Address| {
+            Address::Expression(Box::new(left), operator, Box::new(right))
+        };
+        let operands = vec![
+            AddressOperand::new(
+                SimpleAddressOperand::DirectAddress(expression(
+                    expression(
+                        Address::Identifier("TABLE".into()),
+                        Operator::Add,
+                        Address::NumericAddress(2),
+                    ),
+                    Operator::Multiply,
+                    Address::NumericAddress(3),
+                )),
+                Some(4),
+            ),
+            AddressOperand::new(
+                SimpleAddressOperand::DirectAddress(expression(
+                    Address::Identifier("END".into()),
+                    Operator::Subtract,
+                    expression(
+                        Address::Identifier("START".into()),
+                        Operator::Subtract,
+                        Address::NumericAddress(2),
+                    ),
+                )),
+                None,
+            ),
+            AddressOperand::new(
+                SimpleAddressOperand::DirectAddress(expression(
+                    Address::Identifier("A".into()),
+                    Operator::Multiply,
+                    Address::Identifier("B".into()),
+                )),
+                None,
+            ),
+        ];
+        let actual_operands = actual[1..]
+            .iter()
+            .map(|line| match line.as_ref().unwrap().source_program_word() {
+                Some(SourceWord::PWord(pword)) => match pword.store_operand() {
+                    StoreOperand::AddressOperand(operand) => operand,
+                    other => panic!("Unexpected operand {:?}", other),
+                },
+                other => panic!("Unexpected word {:?}", other),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(actual_operands, operands);
+
+        let written = actual[1..]
+            .iter()
+            .map(|line| line.as_ref().unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert!(written[0].contains("(TABLE+2)*3(4)"));
+        assert!(written[1].contains("END-(START-2)"));
+        assert!(written[2].contains("A*B"));
+    }
+
     #[test]
     fn sword() {
         let program = r#"0001   "TEXT"
@@ -235,14 +484,30 @@ mod test {
     fn fword() {
         let program = r#"
 0001   3.14
+0002   -1.05
+0003   .005
 "#;
         let actual = parse(program);
-        let expected = vec![Ok(SourceLine::new(
-            1,
-            None.into(),
-            Some(SourceWord::FWord(3.14)),
-            "".into(),
-        ))];
+        let expected = vec![
+            Ok(SourceLine::new(
+                1,
+                None.into(),
+                Some(SourceWord::FWord(3.14)),
+                "".into(),
+            )),
+            Ok(SourceLine::new(
+                2,
+                None.into(),
+                Some(SourceWord::FWord(-1.05)),
+                "".into(),
+            )),
+            Ok(SourceLine::new(
+                3,
+                None.into(),
+                Some(SourceWord::FWord(0.005)),
+                "".into(),
+            )),
+        ];
         assert_eq!(actual[1..], expected)
     }
 
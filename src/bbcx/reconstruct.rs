@@ -0,0 +1,559 @@
+use super::assembler::{Assembler, DEFAULT_ORIGIN};
+use super::assembly::Location;
+use super::ast::{Identifier, Mnemonic, SourceLine};
+use super::disassembler::{
+    has_address_operand, instruction, is_initial_word, is_label, library_routine, source,
+    word_error,
+};
+use super::flow::writes;
+use super::memory::{Instruction, MemoryConfig, MemoryIndex, Word, WordType};
+use super::object::ObjectImage;
+use super::parser::Parser;
+
+use crate::result::{Error, Result};
+
+use std::collections::hash_map::Entry as MapEntry;
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+// ****************************************************************************
+// The historical programs are assembled listings, with a numeric address on
+// every line and no labels. The assembler kept its identifiers and literals in
+// a pool at the top of memory, above a gap after the program:
+//
+//     1008    NIL     49          a label, as a pointer to its location,
+//     1009    "MD"                followed by its name
+//     1018                        a variable, with storage but no value,
+//     1019    "PNT"               followed by its name
+//     1020    -1                  a literal, alone
+//
+// The program reaches a label indirectly, as in `JUMP *1008`, and a variable or
+// literal directly. Each of these is turned back into its symbolic form, so that
+// `JUMP *1008` becomes `JUMP 0, MD`, `DECR 1018` becomes `DECR 0, PNT`, and
+// `SUBT 1, 1020` becomes `SUBT 1, -1`. The assembler allocates the variables and
+// literals again. Other locations that are used as operands are given labels
+// such as L0017. Words in the pool that are not recognised are kept together in
+// the order they had, just below the storage that the assembler allocates, as
+// the program may step through them.
+
+const MARKER: &str = "IDENTIFIER/LITERAL";
+
+#[derive(Clone, Debug, PartialEq)]
+enum Entry {
+    Literal(String),
+    Pointer(Identifier),
+    Variable(Identifier),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Reference {
+    target: Location,
+    indirect: bool,
+    indexed: bool,
+    writes: bool,
+}
+
+/// Readable source for an assembled listing that uses numeric addresses.
+pub fn reconstruct(ast: &[SourceLine]) -> Result<Vec<String>> {
+    let memory_size = MemoryConfig::default().size();
+    let assembly = Assembler::default().build(ast)?;
+    let image = ObjectImage::new(assembly, memory_size)?;
+    let words = image
+        .words()
+        .iter()
+        .filter(|(location, word)| !is_initial_word(*location, word))
+        .copied()
+        .collect();
+
+    let lines = Program::new(words).lines(&Comments::new(ast), memory_size)?;
+    verify(&lines)?;
+    Ok(lines)
+}
+
+struct Program {
+    words: BTreeMap<Location, Word>,
+    pool_start: Location,
+    entries: BTreeMap<Location, Entry>,
+    consumed: HashSet<Location>,
+    labels: HashMap<Location, Identifier>,
+}
+
+impl Program {
+    fn new(words: BTreeMap<Location, Word>) -> Self {
+        let pool_start = pool_start(&words);
+        let mut program = Self {
+            words,
+            pool_start,
+            entries: BTreeMap::new(),
+            consumed: HashSet::new(),
+            labels: HashMap::new(),
+        };
+        program.classify_pool();
+        program.label_targets();
+        program
+    }
+
+    fn references(&self, in_pool: bool) -> Vec<Reference> {
+        self.words
+            .iter()
+            .filter(|(location, _)| (**location >= self.pool_start) == in_pool)
+            .filter_map(|(_, word)| instruction(word))
+            .filter(has_address_operand)
+            .map(|instruction| Reference {
+                target: instruction.address().memory_index(),
+                indirect: instruction.is_indirect(),
+                indexed: instruction.index_register().is_indexable(),
+                writes: writes(instruction.function()),
+            })
+            .collect()
+    }
+
+    fn classify_pool(&mut self) {
+        let mut uses: BTreeMap<Location, Vec<Reference>> = BTreeMap::new();
+        for reference in self.references(false) {
+            if reference.target >= self.pool_start {
+                uses.entry(reference.target).or_default().push(reference);
+            }
+        }
+        // Words in the pool that are reached from the pool must stay in place.
+        let pinned = self
+            .references(true)
+            .into_iter()
+            .map(|reference| reference.target)
+            .collect::<HashSet<_>>();
+
+        let mut names = HashSet::new();
+        for (location, references) in &uses {
+            if pinned.contains(location) || self.consumed.contains(location) {
+                continue;
+            }
+            let word = self.words.get(location);
+            let entry = match (self.name(*location, &uses, &pinned), word) {
+                (Some(name), _) => {
+                    self.consumed.insert(location + 1);
+                    let usable = |name: &Identifier| is_label(name) && !names.contains(name);
+                    match self.pointer_target(word, references) {
+                        Some(target) => {
+                            let name = Some(name)
+                                .filter(usable)
+                                .unwrap_or_else(|| unique(&format!("L{:04}", target), &names));
+                            Entry::Pointer(self.labels.entry(target).or_insert(name).clone())
+                        }
+                        None => Entry::Variable(
+                            Some(name)
+                                .filter(usable)
+                                .unwrap_or_else(|| unique(&format!("L{:04}", location), &names)),
+                        ),
+                    }
+                }
+                (None, Some(word)) if is_literal(word, references) => {
+                    Entry::Literal(source(word, &|_| String::new()).unwrap_or_default())
+                }
+                (None, None) => Entry::Variable(unique(&format!("L{:04}", location), &names)),
+                (None, Some(_)) => continue,
+            };
+            if let Entry::Pointer(name) | Entry::Variable(name) = &entry {
+                names.insert(name.clone());
+            }
+            self.consumed.insert(*location);
+            self.entries.insert(*location, entry);
+        }
+    }
+
+    // The name of an identifier is the S-word after its storage. A name that
+    // cannot be used as a label, such as a mnemonic, is replaced.
+    fn name(
+        &self,
+        location: Location,
+        uses: &BTreeMap<Location, Vec<Reference>>,
+        pinned: &HashSet<Location>,
+    ) -> Option<Identifier> {
+        let at = location + 1;
+        let word = self.words.get(&at).filter(|word| word.is_sword())?;
+        let name = word.as_string().ok()?;
+        (!uses.contains_key(&at) && !pinned.contains(&at)).then_some(name)
+    }
+
+    // A label is a pointer to its location, which is only used indirectly.
+    fn pointer_target(&self, word: Option<&Word>, references: &[Reference]) -> Option<Location> {
+        let instruction = word.and_then(instruction)?;
+        let target = instruction.address().memory_index();
+        let is_pointer = instruction.function() == Mnemonic::NIL
+            && !instruction.is_indirect()
+            && !instruction.index_register().is_indexable()
+            && references
+                .iter()
+                .all(|reference| reference.indirect && !reference.writes);
+        let is_in_program =
+            (DEFAULT_ORIGIN..self.pool_start).contains(&target) && self.words.contains_key(&target);
+        (is_pointer && is_in_program).then_some(target)
+    }
+
+    fn label_targets(&mut self) {
+        let mut names = self
+            .labels
+            .values()
+            .chain(self.entries.values().filter_map(|entry| match entry {
+                Entry::Variable(name) => Some(name),
+                _ => None,
+            }))
+            .cloned()
+            .collect::<HashSet<_>>();
+        let mut targets = self
+            .references(false)
+            .into_iter()
+            .chain(self.references(true))
+            .map(|reference| reference.target)
+            .filter(|target| *target >= DEFAULT_ORIGIN && !self.consumed.contains(target))
+            .filter(|target| *target >= self.pool_start || self.words.contains_key(target))
+            .collect::<Vec<_>>();
+        targets.sort();
+        targets.dedup();
+        for target in targets {
+            if let MapEntry::Vacant(entry) = self.labels.entry(target) {
+                let label = unique(&format!("L{:04}", target), &names);
+                names.insert(label.clone());
+                entry.insert(label);
+            }
+        }
+    }
+
+    fn operand(&self, instruction: &Instruction) -> String {
+        let address = instruction.address().memory_index();
+        let indirect = instruction.indirect();
+        let index = instruction.index_register();
+        if !has_address_operand(instruction) {
+            return format!("{}{}{}", indirect, address, index);
+        }
+        match self.entries.get(&address) {
+            Some(Entry::Literal(literal)) => literal.clone(),
+            Some(Entry::Pointer(name)) => format!("{}{}", name, index),
+            Some(Entry::Variable(name)) => format!("{}{}{}", indirect, name, index),
+            None => match self.labels.get(&address) {
+                Some(label) => format!("{}{}{}", indirect, label, index),
+                None => format!("{}{}{}", indirect, address, index),
+            },
+        }
+    }
+
+    fn source(&self, location: Location, word: &Word) -> std::result::Result<String, String> {
+        source(word, &|instruction| self.operand(instruction))
+            .ok_or_else(|| word_error(location, word, "cannot be written as source"))
+    }
+
+    // Runs of pool words that are kept, with the free locations between them.
+    fn runs(&self) -> Vec<(Location, Location)> {
+        let mut kept = self
+            .words
+            .keys()
+            .chain(self.labels.keys())
+            .copied()
+            .filter(|location| *location >= self.pool_start && !self.consumed.contains(location))
+            .collect::<Vec<_>>();
+        kept.sort();
+        kept.dedup();
+
+        let mut runs: Vec<(Location, Location)> = Vec::new();
+        for location in kept {
+            match runs.last_mut() {
+                Some((_, last)) if (*last + 1..location).all(|l| !self.consumed.contains(&l)) => {
+                    *last = location
+                }
+                _ => runs.push((location, location)),
+            }
+        }
+        runs
+    }
+
+    fn lines(&self, comments: &Comments, memory_size: usize) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        let mut errors = Vec::new();
+        let mut notes = comments.notes.iter().peekable();
+        let mut counter = DEFAULT_ORIGIN;
+        for (location, word) in self.words.range(..self.pool_start) {
+            while let Some((_, note)) = notes.next_if(|(at, _)| at <= location) {
+                lines.push(note.clone());
+            }
+            if *location != counter {
+                lines.push(format!("{:<8}{:<8}{}", "", "ORIGIN", location));
+            }
+            match self.source(*location, word) {
+                Ok(source) => lines.push(line(
+                    self.labels.get(location),
+                    &source,
+                    &comments.of(*location, word),
+                )),
+                Err(error) => errors.push(error),
+            }
+            counter = location + 1;
+        }
+        lines.extend(notes.map(|(_, note)| note.clone()));
+
+        let data = self.data(comments, &mut errors);
+        if !data.is_empty() {
+            let origin = memory_size - self.storage() - self.data_size();
+            lines.push(format!("{:<8}{:<8}{}", "", "ORIGIN", origin));
+            lines.extend(data);
+        }
+
+        if errors.is_empty() {
+            Ok(lines)
+        } else {
+            Err(Error::FailedToDisassemble(errors))
+        }
+    }
+
+    // Variables with a value, then the runs of pool words that are kept.
+    fn data(&self, comments: &Comments, errors: &mut Vec<String>) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (location, entry) in &self.entries {
+            if let (Entry::Variable(name), Some(word)) = (entry, self.words.get(location)) {
+                match self.source(*location, word) {
+                    Ok(source) => {
+                        lines.push(line(Some(name), &source, &comments.of(*location, word)))
+                    }
+                    Err(error) => errors.push(error),
+                }
+            }
+        }
+
+        for (first, last) in self.runs() {
+            let mut reserved: Option<(Option<&Identifier>, usize)> = None;
+            for location in first..=last {
+                let label = self.labels.get(&location);
+                match (self.words.get(&location), reserved.as_mut()) {
+                    (None, Some((_, size))) if label.is_none() => *size += 1,
+                    (None, _) => {
+                        lines.extend(reserved.take().map(reserve));
+                        reserved = Some((label, 1));
+                    }
+                    (Some(word), _) => {
+                        lines.extend(reserved.take().map(reserve));
+                        match self.source(location, word) {
+                            Ok(source) => {
+                                lines.push(line(label, &source, &comments.of(location, word)))
+                            }
+                            Err(error) => errors.push(error),
+                        }
+                    }
+                }
+            }
+            lines.extend(reserved.take().map(reserve));
+        }
+        lines
+    }
+
+    fn data_size(&self) -> usize {
+        let variables = self
+            .entries
+            .iter()
+            .filter(|(location, entry)| {
+                matches!(entry, Entry::Variable(_)) && self.words.contains_key(location)
+            })
+            .count();
+        let runs = self
+            .runs()
+            .iter()
+            .map(|(first, last)| last - first + 1)
+            .sum::<usize>();
+        variables + runs
+    }
+
+    // The words the assembler allocates for variables without a value and for
+    // each different literal.
+    fn storage(&self) -> usize {
+        let variables = self
+            .entries
+            .iter()
+            .filter(|(location, entry)| {
+                matches!(entry, Entry::Variable(_)) && !self.words.contains_key(location)
+            })
+            .count();
+        let literals = self
+            .entries
+            .values()
+            .filter_map(|entry| match entry {
+                Entry::Literal(literal) => Some(literal),
+                _ => None,
+            })
+            .collect::<HashSet<_>>()
+            .len();
+        variables + literals
+    }
+}
+
+// The pool starts above the largest gap between the words of the program, or
+// just below, with storage for an identifier that has no value.
+fn pool_start(words: &BTreeMap<Location, Word>) -> Location {
+    let locations = words.keys().copied().collect::<Vec<_>>();
+    let Some(first) = locations
+        .windows(2)
+        .filter(|pair| pair[1] - pair[0] > 1)
+        .max_by_key(|pair| pair[1] - pair[0])
+        .map(|pair| pair[1])
+    else {
+        return Location::MAX;
+    };
+    let is_storage = words.values().filter_map(instruction).any(|instruction| {
+        has_address_operand(&instruction) && instruction.address().memory_index() == first - 1
+    });
+    if is_storage {
+        first - 1
+    } else {
+        first
+    }
+}
+
+// A literal is only ever read, directly.
+fn is_literal(word: &Word, references: &[Reference]) -> bool {
+    matches!(
+        word.content_type(),
+        WordType::IWord | WordType::FWord | WordType::SWord
+    ) && references
+        .iter()
+        .all(|reference| !reference.indirect && !reference.indexed && !reference.writes)
+}
+
+fn unique(name: &str, names: &HashSet<Identifier>) -> Identifier {
+    let mut name = name.to_string();
+    while names.contains(&name) {
+        name.insert(0, 'L');
+    }
+    name
+}
+
+fn line(label: Option<&Identifier>, source: &str, comment: &str) -> String {
+    let label = label.map_or(String::new(), |label| format!("{}:", label));
+    let text = format!("{:<8}{}", label, source);
+    if comment.is_empty() {
+        text
+    } else {
+        format!("{:<27} {}", text, comment)
+    }
+}
+
+fn reserve((label, size): (Option<&Identifier>, usize)) -> String {
+    line(label, &format!("{:<8}{}", "RESERVE", size), "")
+}
+
+// The comments of a listing, other than the marker of an operand in the pool.
+// Comments on lines without a location are not kept.
+struct Comments {
+    words: HashMap<Location, String>,
+    notes: Vec<(Location, String)>,
+}
+
+impl Comments {
+    fn new(ast: &[SourceLine]) -> Self {
+        let mut words = HashMap::new();
+        let mut notes = Vec::new();
+        for line in ast {
+            let Some(location) = line.location() else {
+                continue;
+            };
+            let comment = line.comment().replace(MARKER, "");
+            let comment = comment.trim_end().trim_end_matches(';').trim_end();
+            if comment.is_empty() {
+                continue;
+            }
+            if line.source_program_word().is_some() {
+                words.insert(location, comment.to_string());
+            } else if line.directive().is_none() {
+                notes.push((location, comment.to_string()));
+            }
+        }
+        Self { words, notes }
+    }
+
+    // A library routine is written with its own mnemonic, so a comment that
+    // only names it is not needed.
+    fn of(&self, location: Location, word: &Word) -> String {
+        let comment = self.words.get(&location).map_or("", String::as_str);
+        let is_routine = instruction(word)
+            .and_then(|i| library_routine(&i))
+            .is_some();
+        let is_name = comment.trim_start_matches(';').split_whitespace().count() == 1;
+        if is_routine && is_name {
+            String::new()
+        } else {
+            comment.to_string()
+        }
+    }
+}
+
+fn verify(lines: &[String]) -> Result<()> {
+    let error = |error: Error| {
+        Error::FailedToDisassemble(vec![format!(
+            "reconstructed source does not assemble: {}",
+            error
+        )])
+    };
+    let ast = lines
+        .iter()
+        .map(|line| Parser::parse_line(line))
+        .collect::<Result<Vec<_>>>()
+        .map_err(error)?;
+    let assembly = Assembler::default().build(&ast).map_err(error)?;
+    ObjectImage::new(assembly, MemoryConfig::default().size()).map_err(error)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn parse(input: &str) -> Vec<SourceLine> {
+        input
+            .lines()
+            .map(|line| Parser::parse_line(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn will_reconstruct_labels_identifiers_and_literals() {
+        let ast = parse(
+            r#"0009    ;"DEMO"
+0016    TAKE    5, 1021     ; IDENTIFIER/LITERAL
+0017    PRINT   5
+0018    DECR    1018        ; IDENTIFIER/LITERAL
+0019    JNZ     *1016       ; IDENTIFIER/LITERAL
+0020    JUMP    17          ; BACK
+0021    EXTRA   10          ; STOP
+1016    NIL     17
+1017    "LOOP"
+1019    "CNT"
+1020    "VAR"
+1021    -1"#,
+        );
+        assert_eq!(
+            reconstruct(&ast).unwrap(),
+            vec![
+                ";\"DEMO\"",
+                "        ORIGIN  16",
+                "        TAKE    5, -1",
+                "LOOP:   PRINT   5",
+                "        DECR    1, CNT",
+                "        JNZ     1, LOOP",
+                "        JUMP    1, LOOP     ; BACK",
+                "        STOP    1",
+                "        ORIGIN  1021",
+                "        \"VAR\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_reconstruct_historical_programs() {
+        let folder = std::path::Path::new("./examples/sir_fredric_osborn_school_wgc_may_1974");
+        for program in ["alph", "area", "base", "sort", "sqrt"] {
+            let path = folder.join(program).with_extension("bbc");
+            let ast = parse(&std::fs::read_to_string(&path).unwrap());
+            let lines = reconstruct(&ast).unwrap();
+            assert!(
+                lines.iter().all(|line| !line.contains(MARKER)),
+                "{}",
+                program
+            );
+        }
+    }
+}
@@ -10,6 +10,9 @@ pub enum Error {
 
     #[error("failed to create execution context: reason {0}")]
     FailedToCreateExecutionContext(String),
+
+    #[error("device fault: {0}")]
+    DeviceFault(String),
 }
 
 pub(crate) type Result<T> = std::result::Result<T, Error>;
@@ -1,9 +1,11 @@
-use crate::args::{Args, Language as LanguageArg};
+use crate::args::{Args, Command, Language as LanguageArg};
 use crate::bbc3::Bbc3;
 use crate::bbcx::BbcX;
 use crate::language::Language;
 use crate::result::{Error, Result};
 
+use std::path::{Path, PathBuf};
+
 pub struct Core {}
 
 impl Core {
@@ -13,6 +15,25 @@ impl Core {
             LanguageArg::BbcX => Language::BbcX(BbcX::new(args)),
         };
 
+        if let Some(command) = args.command() {
+            return Core::command(&language, command);
+        }
+
+        if args.link() {
+            let files = args.files().collect::<Vec<_>>();
+            for file in &files {
+                let _ = language.list(file);
+            }
+            return language.link(&files).and_then(|_| {
+                if args.run() {
+                    let trace_path = trace_path(args, &files[0]);
+                    language.run_linked(&files, trace_path.as_deref())
+                } else {
+                    Ok(())
+                }
+            });
+        }
+
         let mut results = vec![];
 
         for file in args.files() {
@@ -20,12 +41,7 @@ impl Core {
 
             let result = language.assemble(&file).and_then(|_| {
                 if args.run() {
-                    let trace_path = (args.trace()).then_some({
-                        let parent = file.parent().unwrap().to_path_buf();
-                        let parent = args.trace_path().unwrap_or(parent);
-                        let stem = file.file_stem().unwrap();
-                        parent.join(stem).with_extension("out")
-                    });
+                    let trace_path = trace_path(args, &file);
                     language.run(&file, trace_path.as_deref())
                 } else {
                     Ok(())
@@ -45,6 +61,53 @@ impl Core {
             Err(Error::BuildErrors(results))
         }
     }
+
+    fn command(language: &Language, command: &Command) -> Result<()> {
+        let results = match command {
+            Command::Disasm { files } => files
+                .iter()
+                .map(|file| language.disassemble(file))
+                .collect::<Vec<_>>(),
+            Command::Reconstruct { files } => files
+                .iter()
+                .map(|file| language.reconstruct(file))
+                .collect::<Vec<_>>(),
+            Command::Fmt { check, files } => files
+                .iter()
+                .map(|file| language.format(file, *check))
+                .collect::<Vec<_>>(),
+            Command::Lint { files } => files
+                .iter()
+                .map(|file| language.lint(file))
+                .collect::<Vec<_>>(),
+            Command::Cfg { files } => files
+                .iter()
+                .map(|file| language.graph(file))
+                .collect::<Vec<_>>(),
+        };
+
+        let results = results
+            .into_iter()
+            .filter_map(Result::err)
+            .collect::<Vec<Error>>();
+
+        if results.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::BuildErrors(results))
+        }
+    }
+}
+
+// The trace of a program is written next to its source file, unless a folder
+// for traces is given.
+fn trace_path(args: &Args, file: &Path) -> Option<PathBuf> {
+    (args.trace()).then_some({
+        let parent = file.parent().unwrap().to_path_buf();
+        let parent = args.trace_path().unwrap_or(parent);
+        let stem = file.file_stem().unwrap();
+        parent.join(stem).with_extension("out")
+    })
 }
 
 #[cfg(test)]
@@ -3,7 +3,7 @@ use crate::bbcx::BbcX;
 use crate::model::LanguageModel;
 use crate::result::Result;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub enum Language {
     Bbc3(Bbc3),
@@ -31,4 +31,53 @@ impl Language {
             Language::BbcX(model) => model.list(file),
         }
     }
+
+    pub fn link(&self, files: &[PathBuf]) -> Result<()> {
+        match self {
+            Language::Bbc3(model) => model.link(files),
+            Language::BbcX(model) => model.link(files),
+        }
+    }
+
+    pub fn run_linked(&self, files: &[PathBuf], trace: Option<&Path>) -> Result<()> {
+        match self {
+            Language::Bbc3(model) => model.run_linked(files, trace),
+            Language::BbcX(model) => model.run_linked(files, trace),
+        }
+    }
+
+    pub fn disassemble(&self, file: &Path) -> Result<()> {
+        match self {
+            Language::Bbc3(model) => model.disassemble(file),
+            Language::BbcX(model) => model.disassemble(file),
+        }
+    }
+
+    pub fn reconstruct(&self, file: &Path) -> Result<()> {
+        match self {
+            Language::Bbc3(model) => model.reconstruct(file),
+            Language::BbcX(model) => model.reconstruct(file),
+        }
+    }
+
+    pub fn format(&self, file: &Path, check: bool) -> Result<()> {
+        match self {
+            Language::Bbc3(model) => model.format(file, check),
+            Language::BbcX(model) => model.format(file, check),
+        }
+    }
+
+    pub fn lint(&self, file: &Path) -> Result<()> {
+        match self {
+            Language::Bbc3(model) => model.lint(file),
+            Language::BbcX(model) => model.lint(file),
+        }
+    }
+
+    pub fn graph(&self, file: &Path) -> Result<()> {
+        match self {
+            Language::Bbc3(model) => model.graph(file),
+            Language::BbcX(model) => model.graph(file),
+        }
+    }
 }
@@ -1,20 +1,56 @@
 use crate::args::Args;
+use crate::pager::Pager;
 
 use std::path::{Path, PathBuf};
 
+// ****************************************************************************
+// A listing is laid out in pages for the line printer, in the style of the
+// DECsystem-10 assemblers. Each page starts with a header of the file name, the
+// date and the page number, then the section of the listing, such as SOURCE,
+// SYMBOLS or STORAGE MAP, then a blank line. Pages are of a fixed length and
+// width, and are separated by form feeds. Source lines are numbered by their
+// line in the source file; other lines, such as error marks and the sections
+// after the source, are not numbered. A line that is too wide is folded onto
+// the next line. Each section starts on a new page. Errors are marked in the
+// listing, and summarised at the end with the page and source line of each.
+// Pages are counted by the same pager as the line printer device.
+
 pub struct ListWriter {
     list_file: Option<PathBuf>,
+    title: String,
+    page_width: usize,
+    section: String,
     listing: Vec<String>,
+    pager: Pager,
+    errors: Vec<(usize, Option<usize>, String)>,
 }
 
 impl ListWriter {
+    const HEADER_LINES: usize = 3;
+    const NUMBER_WIDTH: usize = 6;
+    const ERROR_MARKER: &'static str = " *****  ";
+
     pub fn new(file: &Path, args: &Args) -> Self {
         let list_file = ListWriter::get_list_file(file, args);
-        let listing = Vec::new();
-        let mut me = Self { list_file, listing };
-        me.add_title_to_listing(file);
-        me.add_line_to_listing("");
-        me
+        let title = format!("{}    {}", file.display(), ListWriter::date());
+        ListWriter::with_pages(list_file, &title, args.page_length(), args.page_width())
+    }
+
+    fn with_pages(
+        list_file: Option<PathBuf>,
+        title: &str,
+        page_length: usize,
+        page_width: usize,
+    ) -> Self {
+        Self {
+            list_file,
+            title: title.to_uppercase(),
+            page_width: page_width.max(ListWriter::NUMBER_WIDTH + 1),
+            section: "SOURCE".into(),
+            listing: Vec::new(),
+            pager: Pager::new(page_length.max(ListWriter::HEADER_LINES + 1)),
+            errors: Vec::new(),
+        }
     }
 
     fn get_list_file(file: &Path, args: &Args) -> Option<PathBuf> {
@@ -29,35 +65,218 @@ impl ListWriter {
         }
     }
 
-    fn add_title_to_listing(&mut self, file: &Path) {
-        let filename = file.display().to_string();
-        let format = time::format_description::parse(
+    fn date() -> String {
+        let format = time::format_description::parse_borrowed::<2>(
             "[weekday repr:short] [day] [month repr:short] [year] [hour]:[minute]",
         )
         .unwrap();
         let now = time::OffsetDateTime::now_utc();
         let offset = time::UtcOffset::local_offset_at(now).unwrap();
-        let now = now.to_offset(offset).format(&format).unwrap().to_string();
-        self.add_line_to_listing(&format!("{:<14}{:<42} {}\n", "", filename, now).to_uppercase());
+        now.to_offset(offset).format(&format).unwrap().to_string()
+    }
+
+    /// Start a section of the listing on a new page.
+    pub fn start_section(&mut self, section: &str) {
+        self.section = section.to_uppercase();
+        self.pager.throw_page();
+    }
+
+    /// Add a line of the source, numbered by its line in the source file.
+    pub fn add_source_to_listing(&mut self, line: usize, text: &str) {
+        self.add_line_to_listing(&line.to_string(), text);
     }
 
+    /// Add lines that are not numbered, such as those of the sections after the
+    /// source.
     pub fn add_lines_to_listing(&mut self, text: &str) {
         let text = text.split('\n');
-        text.into_iter().for_each(|s| self.add_line_to_listing(s));
+        text.into_iter()
+            .for_each(|s| self.add_line_to_listing("", s));
     }
 
-    fn add_line_to_listing(&mut self, text: &str) {
-        let line_number = self.listing.len() + 1;
-        let text = format!("{:>5} {}", line_number, text);
-        self.listing.push(text.trim_end().into())
+    /// Mark an error in the listing, at the source line that it is found on when
+    /// there is one, with any further lines of it aligned with the source.
+    pub fn add_error_to_listing(&mut self, line: Option<usize>, error: &str) {
+        let message = error
+            .lines()
+            .rev()
+            .map(|line| line.trim().trim_start_matches('^').trim_start())
+            .find(|line| !line.is_empty())
+            .unwrap_or_default()
+            .to_string();
+        self.errors.push((self.pager.next_page(), line, message));
+        let error = error.replace('\n', "\n        ");
+        self.add_lines_to_listing(&format!("{}{}", ListWriter::ERROR_MARKER, error));
+    }
+
+    fn add_line_to_listing(&mut self, number: &str, text: &str) {
+        let text = format!(
+            "{:>width$}{}",
+            format!("{} ", number),
+            text,
+            width = ListWriter::NUMBER_WIDTH
+        );
+        let text = text.trim_end().chars().collect::<Vec<_>>();
+        let continuation = self.page_width - ListWriter::NUMBER_WIDTH;
+        let (first, mut rest) = text.split_at(text.len().min(self.page_width));
+        self.print(first.iter().collect());
+        while !rest.is_empty() {
+            let (folded, remaining) = rest.split_at(rest.len().min(continuation));
+            let folded = folded.iter().collect::<String>();
+            self.print(format!(
+                "{:width$}{}",
+                "",
+                folded,
+                width = ListWriter::NUMBER_WIDTH
+            ));
+            rest = remaining;
+        }
+    }
+
+    fn print(&mut self, line: String) {
+        if self.pager.is_top_of_page() {
+            self.start_page();
+        }
+        self.listing.push(line);
+        self.pager.end_line();
+    }
+
+    fn start_page(&mut self) {
+        let form_feed = if self.pager.start_page(ListWriter::HEADER_LINES) {
+            Pager::FORM_FEED.to_string()
+        } else {
+            String::new()
+        };
+        let number = format!("PAGE {:>4}", self.pager.page());
+        let width = self.page_width.saturating_sub(number.len());
+        let header = format!("{:<width$}{}", self.title, number, width = width);
+        let header = header.chars().take(self.page_width).collect::<String>();
+        self.listing.push(format!("{}{}", form_feed, header));
+        self.listing.push(self.section.clone());
+        self.listing.push(String::new());
+    }
+
+    // The errors are summarised in a section of their own.
+    fn add_error_summary(&mut self) {
+        if self.errors.is_empty() {
+            return;
+        }
+        let errors = std::mem::take(&mut self.errors);
+        self.start_section("ERRORS");
+        let count = match errors.len() {
+            1 => "1 ERROR".to_string(),
+            count => format!("{} ERRORS", count),
+        };
+        self.add_lines_to_listing(&format!("        {} DETECTED", count));
+        for (page, line, message) in &errors {
+            let line = line.map_or(String::new(), |line| format!("LINE {:>5}", line));
+            self.add_lines_to_listing(&format!(
+                "        PAGE {:>4}  {:<10}  {}",
+                page, line, message
+            ));
+        }
     }
 
     #[inline]
-    pub fn write_content_to_file(&self) -> std::result::Result<(), std::io::Error> {
+    pub fn write_content_to_file(mut self) -> std::result::Result<(), std::io::Error> {
+        self.add_error_summary();
         if let Some(list_file) = &self.list_file {
-            std::fs::write(list_file, self.listing.join("\n"))
+            std::fs::write(list_file, self.listing.join("\n") + "\n")
         } else {
             Ok(())
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+    use tempdir::TempDir;
+
+    fn listing(writer: ListWriter) -> Vec<String> {
+        let mut writer = writer;
+        writer.add_error_summary();
+        writer.listing
+    }
+
+    #[test]
+    fn will_lay_out_sections_on_pages() {
+        let mut writer = ListWriter::with_pages(None, "sort.bbc    Sun 18 Oct 2026", 6, 40);
+        for (line, text) in ["ONE", "TWO", "THREE", "FOUR"].iter().enumerate() {
+            writer.add_source_to_listing(line + 1, &format!("        {}", text));
+        }
+        writer.start_section("Symbols");
+        writer.add_lines_to_listing("        NAME");
+        assert_eq!(
+            listing(writer),
+            vec![
+                "SORT.BBC    SUN 18 OCT 2026    PAGE    1",
+                "SOURCE",
+                "",
+                "    1         ONE",
+                "    2         TWO",
+                "    3         THREE",
+                "\x0cSORT.BBC    SUN 18 OCT 2026    PAGE    2",
+                "SOURCE",
+                "",
+                "    4         FOUR",
+                "\x0cSORT.BBC    SUN 18 OCT 2026    PAGE    3",
+                "SYMBOLS",
+                "",
+                "              NAME",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_fold_wide_lines() {
+        let mut writer = ListWriter::with_pages(None, "f", 10, 20);
+        writer.add_source_to_listing(1, "        0123456789ABCDEF");
+        assert_eq!(
+            listing(writer)[3..],
+            vec!["    1         012345", "      6789ABCDEF"]
+        );
+    }
+
+    #[test]
+    fn will_summarise_errors() {
+        let mut writer = ListWriter::with_pages(None, "f", 6, 60);
+        writer.add_source_to_listing(1, "        TAKE 1, 2");
+        writer.add_source_to_listing(2, "        TAKE 1, 3");
+        writer.add_error_to_listing(Some(3), "TAKE 9, 1\n^ expected accumulator");
+        writer.add_source_to_listing(4, "        STOP");
+        writer.add_error_to_listing(None, "undefined identifier");
+        assert_eq!(
+            listing(writer)[3..],
+            vec![
+                "    1         TAKE 1, 2",
+                "    2         TAKE 1, 3",
+                "       *****  TAKE 9, 1",
+                "\x0cF                                                  PAGE    2",
+                "SOURCE",
+                "",
+                "              ^ expected accumulator",
+                "    4         STOP",
+                "       *****  undefined identifier",
+                "\x0cF                                                  PAGE    3",
+                "ERRORS",
+                "",
+                "              2 ERRORS DETECTED",
+                "              PAGE    1  LINE     3  expected accumulator",
+                "              PAGE    2              undefined identifier",
+            ]
+        );
+    }
+
+    #[test]
+    fn will_end_listing_file_with_newline() {
+        let dir = TempDir::new("list_writer").unwrap();
+        let list_file = dir.path().join("f.lst");
+        let mut writer = ListWriter::with_pages(Some(list_file.clone()), "f", 6, 20);
+        writer.add_source_to_listing(1, "        STOP");
+        writer.write_content_to_file().unwrap();
+        let content = std::fs::read_to_string(list_file).unwrap();
+        assert!(content.ends_with("    1         STOP\n"));
+    }
+}
@@ -5,7 +5,9 @@ pub mod core;
 pub mod language;
 pub mod list_writer;
 pub mod model;
+pub mod pager;
 pub mod result;
+pub mod source_writer;
 
 use crate::args::Args;
 use crate::core::Core;
@@ -16,7 +18,10 @@ fn main() {
     let args = std::env::args().collect::<Vec<String>>();
     match bbc_x(args) {
         Ok(_) => {}
-        Err(e) => println!("Error: {}", e),
+        Err(e) => {
+            println!("Error: {}", e);
+            std::process::exit(1);
+        }
     }
 }
 
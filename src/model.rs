@@ -3,10 +3,17 @@
 ///
 use crate::result::*;
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub trait LanguageModel {
     fn assemble(&self, path: &Path) -> Result<()>;
     fn run(&self, path: &Path, trace: Option<&Path>) -> Result<()>;
     fn list(&self, path: &Path) -> Result<()>;
+    fn link(&self, paths: &[PathBuf]) -> Result<()>;
+    fn run_linked(&self, paths: &[PathBuf], trace: Option<&Path>) -> Result<()>;
+    fn disassemble(&self, path: &Path) -> Result<()>;
+    fn reconstruct(&self, path: &Path) -> Result<()>;
+    fn format(&self, path: &Path, check: bool) -> Result<()>;
+    fn lint(&self, path: &Path) -> Result<()>;
+    fn graph(&self, path: &Path) -> Result<()>;
 }
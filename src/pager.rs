@@ -0,0 +1,100 @@
+// ****************************************************************************
+// Output for the line printer is laid out in pages of a fixed length. A page is
+// started when its first line is printed, rather than when the previous page is
+// full, so output never ends with a form feed. Every page after the first is
+// preceded by a form feed, and can start with header lines, which count towards
+// its length. The pager only counts lines; the printing is left to its user.
+
+#[derive(Debug)]
+pub struct Pager {
+    page_length: usize,
+    page: usize,
+    line: usize,
+    top_of_page: bool,
+}
+
+impl Pager {
+    pub const FORM_FEED: char = '\x0c';
+
+    pub fn new(page_length: usize) -> Self {
+        Self {
+            page_length: page_length.max(1),
+            page: 0,
+            line: 0,
+            top_of_page: true,
+        }
+    }
+
+    /// Whether the next line printed starts a new page.
+    pub fn is_top_of_page(&self) -> bool {
+        self.top_of_page
+    }
+
+    /// The current page, which is zero before any page is started.
+    pub fn page(&self) -> usize {
+        self.page
+    }
+
+    /// The page that the next line is printed on.
+    pub fn next_page(&self) -> usize {
+        if self.top_of_page {
+            self.page + 1
+        } else {
+            self.page
+        }
+    }
+
+    /// Start the next page, with the given number of header lines. Returns
+    /// whether the page must be preceded by a form feed.
+    pub fn start_page(&mut self, header_lines: usize) -> bool {
+        let form_feed = self.page > 0;
+        self.page += 1;
+        self.line = header_lines;
+        self.top_of_page = false;
+        form_feed
+    }
+
+    /// Count a printed line, throwing the page when it is full.
+    pub fn end_line(&mut self) {
+        self.line += 1;
+        if self.line >= self.page_length {
+            self.throw_page();
+        }
+    }
+
+    /// Throw the current page, unless nothing has been printed on it.
+    pub fn throw_page(&mut self) {
+        self.line = 0;
+        self.top_of_page = true;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn will_start_page_when_first_line_is_printed() {
+        let mut pager = Pager::new(3);
+        assert!(pager.is_top_of_page());
+        assert_eq!(pager.next_page(), 1);
+        assert!(!pager.start_page(1));
+        pager.end_line();
+        assert_eq!(pager.next_page(), 1);
+        pager.end_line();
+        assert!(pager.is_top_of_page());
+        assert_eq!((pager.page(), pager.next_page()), (1, 2));
+        assert!(pager.start_page(1));
+        assert_eq!(pager.page(), 2);
+    }
+
+    #[test]
+    fn will_not_throw_blank_page() {
+        let mut pager = Pager::new(3);
+        pager.start_page(0);
+        pager.throw_page();
+        pager.throw_page();
+        assert_eq!(pager.next_page(), 2);
+    }
+}
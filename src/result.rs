@@ -8,17 +8,34 @@ pub enum Error {
     #[error("failed to parse {0}")]
     FailedToParse(String),
 
-    #[error("failed to assemble {0:#?}")]
+    #[error("failed to assemble\n{}", .0.join("\n"))]
     FailedToAssemble(Vec<String>),
 
+    #[error("failed to disassemble\n{}", .0.join("\n"))]
+    FailedToDisassemble(Vec<String>),
+
+    #[error("failed to format\n{}", .0.join("\n"))]
+    FailedToFormat(Vec<String>),
+
+    #[error("lint warnings\n{}", .0.join("\n"))]
+    LintWarnings(Vec<String>),
+
     #[error("failed to run {0}")]
     FailedToRun(String),
 
-    #[error("multiple build errors {0:#?}")]
+    #[error("multiple build errors\n{}", join_errors(.0))]
     BuildErrors(Vec<Error>),
 
     #[error("cannot write file: {0} {1}")]
     CannotToWriteFile(String, String),
 }
 
+fn join_errors(errors: &[Error]) -> String {
+    errors
+        .iter()
+        .map(Error::to_string)
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
 pub type Result<T> = std::result::Result<T, Error>;
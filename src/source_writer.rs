@@ -0,0 +1,36 @@
+use crate::result::{Error, Result};
+
+use std::path::Path;
+
+/// Writes the formatted lines of a source file back to it, when they differ.
+/// When checking, nothing is written, and each line that differs is an error.
+pub fn write_formatted(
+    path: &Path,
+    original: &[String],
+    formatted: &[String],
+    check: bool,
+) -> Result<()> {
+    let filename = path.display().to_string();
+    if check {
+        let unformatted = original
+            .iter()
+            .zip(formatted)
+            .enumerate()
+            .filter(|(_, (original, formatted))| original != formatted)
+            .map(|(i, _)| format!("{}: line {} is not formatted", filename, i + 1))
+            .collect::<Vec<_>>();
+        if unformatted.is_empty() {
+            Ok(())
+        } else {
+            Err(Error::FailedToFormat(unformatted))
+        }
+    } else if original != formatted {
+        let content = formatted
+            .iter()
+            .map(|line| format!("{}\n", line))
+            .collect::<String>();
+        std::fs::write(path, content).map_err(|e| Error::CannotToWriteFile(filename, e.to_string()))
+    } else {
+        Ok(())
+    }
+}